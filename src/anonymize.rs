@@ -0,0 +1,62 @@
+use sha2::{Digest, Sha256};
+
+use crate::{bedrock::BedrockMotd, login::PostLogin, motd::MOTDDescription, scan::ScanResult};
+
+/// Strips everything that points at a particular server, for publishing
+/// statistics. What is left: port, version, protocol, player counts and
+/// the probe outcomes.
+///
+/// The ip becomes a hash keyed with `salt`, so results of the same server
+/// still line up (within one salt) but the ip can't be brute forced back
+/// from the hash without knowing the salt. Keep it secret.
+pub fn anonymize(result: &mut ScanResult, salt: &str) {
+    result.ip = hash_ip(&result.ip, salt);
+
+    result.motd.description = MOTDDescription::Text(String::new());
+    result.motd.favicon = None;
+    result.motd.players.sample = None;
+    result.players_online_names = None;
+    result.favicon_path = None;
+
+    // hostname, plugins and player names say as much as the motd
+    result.query = None;
+
+    // kick messages name the server more often than not
+    if let Some(login) = &mut result.login {
+        login.disconnect_reason = None;
+        if let Some(PostLogin::Disconnected(reason)) = &mut login.post_login {
+            *reason = None;
+        }
+    }
+}
+
+/// Same as [`anonymize`] for a bedrock pong. It carries no ip of its own,
+/// wherever the ip of the server ends up has to go through [`hash_ip`].
+pub fn anonymize_bedrock(motd: &mut BedrockMotd) {
+    motd.motd.clear();
+    motd.sub_motd = None;
+    // stable per server, as good as the ip for finding it again
+    motd.server_id = None;
+}
+
+/// The first 16 bytes of `HMAC-SHA256(salt, ip)` in hex.
+pub fn hash_ip(ip: &str, salt: &str) -> String {
+    hmac_sha256(salt.as_bytes(), ip.as_bytes())[..16].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// of sha256
+const BLOCK_SIZE: usize = 64;
+
+/// RFC 2104, a plain `sha256(salt || ip)` can't tell where the salt ends
+/// and the ip starts.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; BLOCK_SIZE];
+    match key.len() > BLOCK_SIZE {
+        true => block[..32].copy_from_slice(&Sha256::digest(key)),
+        false => block[..key.len()].copy_from_slice(key)
+    }
+    let pad = |byte: u8| block.map(|key| key ^ byte);
+
+    let inner = Sha256::new().chain_update(pad(0x36)).chain_update(message).finalize();
+    Sha256::new().chain_update(pad(0x5c)).chain_update(inner).finalize().into()
+}
@@ -0,0 +1,104 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::{ Buf, BufMut, BytesMut };
+use serde::{Serialize, Deserialize};
+use tokio::time::timeout;
+use tracing::info;
+
+use crate::{scan::{ScanConfig, ScanError, Target}, transport::connect_udp};
+
+pub const BEDROCK_DEFAULT_PORT: u16 = 19132;
+
+// RakNet "offline message" magic, part of every unconnected packet
+const MAGIC: [u8; 16] = [0x00, 0xff, 0xff, 0x00, 0xfe, 0xfe, 0xfe, 0xfe, 0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56, 0x78];
+
+const UNCONNECTED_PING: u8 = 0x01;
+const UNCONNECTED_PONG: u8 = 0x1c;
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The semicolon separated server info a Bedrock server puts in its pong.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BedrockMotd {
+    /// `MCPE` or `MCEE` for education edition.
+    pub edition: String,
+    pub motd: String,
+    pub protocol: u32,
+    pub version: String,
+    pub players_online: u32,
+    pub players_max: u32,
+    // everything below was added in later versions and is missing on old servers
+    pub server_id: Option<String>,
+    pub sub_motd: Option<String>,
+    pub game_mode: Option<String>,
+    pub port_v4: Option<u16>,
+    pub port_v6: Option<u16>,
+    /// [`ScanConfig::label`] of the scan that found it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub campaign: Option<String>
+}
+
+/// Sends a RakNet unconnected ping and parses the pong.
+pub async fn bedrock_scan(target: &Target, config: &ScanConfig) -> Result<BedrockMotd, ScanError> {
+    info!("Scanning {}:{} (bedrock)", target.ip, target.port);
+
+    let socket = connect_udp(target, config).await?;
+
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+
+    let mut ping = BytesMut::new();
+    ping.put_u8(UNCONNECTED_PING);
+    ping.put_u64(time); // echoed back in the pong
+    ping.put(&MAGIC[..]);
+    ping.put_u64(0); // client guid
+
+    socket.send(&ping).await?;
+
+    let mut buffer = [0u8; 1500]; // pongs always fit into a single datagram
+    let len = timeout(TIMEOUT, socket.recv(&mut buffer)).await.map_err(|_| ScanError::Timeout)??;
+
+    let mut motd = parse_pong(&buffer[..len])?;
+    motd.campaign = config.label.clone();
+    Ok(motd)
+}
+
+pub fn parse_pong(mut pong: &[u8]) -> Result<BedrockMotd, ScanError> {
+    // id + time + server guid + magic + string length
+    if pong.len() < 1 + 8 + 8 + 16 + 2 || pong.get_u8() != UNCONNECTED_PONG {
+        return Err(ScanError::MalformedResponse);
+    }
+
+    pong.advance(8); // time
+    pong.advance(8); // server guid
+
+    if pong[..16] != MAGIC {
+        return Err(ScanError::MalformedResponse);
+    }
+    pong.advance(16);
+
+    let len = pong.get_u16() as usize;
+    let info = pong.get(..len).ok_or(ScanError::MalformedResponse)?;
+    let info = String::from_utf8_lossy(info);
+
+    // edition;motd;protocol;version;online;max;server id;sub motd;game mode;game mode id;port v4;port v6;
+    let fields: Vec<&str> = info.split(';').collect();
+    let field = |index: usize| fields.get(index).map(|field| field.to_string());
+    let number = |index: usize| fields.get(index).and_then(|field| field.parse().ok());
+    // old pongs end in a `;`, which leaves an empty field behind
+    let optional = |index: usize| field(index).filter(|field| !field.is_empty());
+
+    Ok(BedrockMotd {
+        edition: field(0).ok_or(ScanError::MalformedResponse)?,
+        motd: field(1).ok_or(ScanError::MalformedResponse)?,
+        protocol: number(2).ok_or(ScanError::MalformedResponse)?,
+        version: field(3).ok_or(ScanError::MalformedResponse)?,
+        players_online: number(4).ok_or(ScanError::MalformedResponse)?,
+        players_max: number(5).ok_or(ScanError::MalformedResponse)?,
+        server_id: optional(6),
+        sub_motd: optional(7),
+        game_mode: optional(8),
+        port_v4: number(10).and_then(|port| u16::try_from(port).ok()),
+        port_v6: number(11).and_then(|port| u16::try_from(port).ok()),
+        campaign: None
+    })
+}
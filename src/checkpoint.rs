@@ -0,0 +1,112 @@
+use std::{collections::{HashMap, HashSet}, io::{self, SeekFrom}, path::Path};
+
+use serde::{Serialize, Deserialize};
+use tokio::{fs::{File, OpenOptions}, io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt}, sync::Mutex};
+
+use crate::scan::{ScanError, ScanResult, Target};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "outcome")]
+pub enum Outcome {
+    Matched,
+    NotMatched,
+    Failed {
+        /// See [`ScanError::kind`].
+        kind: String,
+        /// Whether it is worth retrying, see [`ScanError::is_transient`].
+        transient: bool
+    }
+}
+
+impl Outcome {
+    pub fn of(result: &Result<ScanResult, ScanError>) -> Self {
+        match result {
+            Ok(result) if result.matched => Outcome::Matched,
+            Ok(_) => Outcome::NotMatched,
+            Err(err) => Outcome::Failed { kind: err.kind().to_string(), transient: err.is_transient() }
+        }
+    }
+}
+
+/// One line of the checkpoint file.
+#[derive(Serialize, Deserialize)]
+pub struct CheckpointRecord {
+    pub ip: String,
+    pub port: u16,
+    #[serde(flatten)]
+    pub outcome: Outcome
+}
+
+/// Appends the outcome of every attempted target to a JSON lines file, so
+/// an interrupted scan can pick up where it left off.
+pub struct Checkpoint {
+    file: Mutex<File>
+}
+
+impl Checkpoint {
+    /// Opens the checkpoint, keeping existing records when resuming.
+    pub async fn open(path: &Path, resume: bool) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .append(resume)
+            .truncate(!resume)
+            .open(path)
+            .await?;
+
+        // a crash can leave the last line without its newline, the next
+        // record would be glued onto it and both would be unreadable
+        if resume && file.metadata().await?.len() > 0 {
+            let mut last = [0u8];
+            file.seek(SeekFrom::End(-1)).await?;
+            file.read_exact(&mut last).await?;
+            if last != *b"\n" {
+                file.write_all(b"\n").await?;
+            }
+        }
+
+        Ok(Self { file: Mutex::new(file) })
+    }
+
+    pub async fn record(&self, target: &Target, outcome: Outcome) -> anyhow::Result<()> {
+        let record = CheckpointRecord { ip: target.ip.clone(), port: target.port, outcome };
+
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+
+        // one write per line, so a crash can only ever cut off the last record,
+        // flushed since tokio finishes file writes in the background
+        let mut file = self.file.lock().await;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+
+        Ok(())
+    }
+}
+
+/// Reads a checkpoint and returns the targets a resumed scan can skip.
+///
+/// A target's last record wins, so a failure that later succeeded counts as
+/// done. With `retry_failures` transient failures (timeouts, resets) are not
+/// skipped, permanent ones (refused, not minecraft) always are.
+pub fn load_finished(path: &Path, retry_failures: bool) -> io::Result<HashSet<Target>> {
+    let file = match std::fs::read_to_string(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(err) => return Err(err)
+    };
+
+    let mut outcomes = HashMap::new();
+    for line in file.lines() {
+        // a cut off last line is expected after a crash
+        if let Ok(record) = serde_json::from_str::<CheckpointRecord>(line) {
+            outcomes.insert(Target { ip: record.ip, port: record.port }, record.outcome);
+        }
+    }
+
+    Ok(outcomes.into_iter()
+        .filter(|(_, outcome)| !(retry_failures && matches!(outcome, Outcome::Failed { transient: true, .. })))
+        .map(|(target, _)| target)
+        .collect())
+}
@@ -0,0 +1,68 @@
+use std::{collections::HashMap, sync::Mutex, time::{Duration, Instant}};
+
+use tracing::warn;
+
+// a host that keeps blocking us backs off up to this many times the base cooldown
+const MAX_BACKOFF_SHIFT: u32 = 6;
+
+#[derive(Default)]
+struct HostState {
+    consecutive_failures: u32,
+    /// How often the host was put on cooldown, doubles the next one.
+    strikes: u32,
+    until: Option<Instant>
+}
+
+/// Tracks consecutive connection failures per ip and stops us from hammering
+/// hosts that are actively refusing or resetting us.
+pub struct HostCooldown {
+    threshold: u32,
+    cooldown: Duration,
+    hosts: Mutex<HashMap<String, HostState>>
+}
+
+impl HostCooldown {
+    /// A `threshold` of 0 disables the cooldown.
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self { threshold, cooldown, hosts: Mutex::new(HashMap::new()) }
+    }
+
+    /// Returns how much longer the host is skipped, if it is.
+    pub fn remaining(&self, ip: &str) -> Option<Duration> {
+        let hosts = self.hosts.lock().unwrap();
+        let until = hosts.get(ip)?.until?;
+        until.checked_duration_since(Instant::now())
+    }
+
+    pub fn record_success(&self, ip: &str) {
+        if self.threshold == 0 {
+            return;
+        }
+
+        if let Some(state) = self.hosts.lock().unwrap().get_mut(ip) {
+            state.consecutive_failures = 0;
+            state.strikes = 0;
+        }
+    }
+
+    pub fn record_failure(&self, ip: &str) {
+        if self.threshold == 0 {
+            return;
+        }
+
+        let mut hosts = self.hosts.lock().unwrap();
+        let state = hosts.entry(ip.to_string()).or_default();
+
+        state.consecutive_failures += 1;
+        if state.consecutive_failures < self.threshold {
+            return;
+        }
+
+        let cooldown = self.cooldown * (1 << state.strikes.min(MAX_BACKOFF_SHIFT));
+        warn!("{} failed {} times in a row, skipping its ports for {:?}", ip, state.consecutive_failures, cooldown);
+
+        state.consecutive_failures = 0;
+        state.strikes += 1;
+        state.until = Some(Instant::now() + cooldown);
+    }
+}
@@ -0,0 +1,93 @@
+use std::{collections::BTreeMap, hash::{DefaultHasher, Hash, Hasher}, path::Path};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{input, scan::Target};
+
+// what gets compared for servers in both directories, as (name, pointer into the result)
+const FIELDS: [(&str, &str); 9] = [
+    ("version", "/motd/version/name"),
+    ("protocol", "/motd/version/protocol"),
+    ("players_online", "/motd/players/online"),
+    ("players_max", "/motd/players/max"),
+    ("description", "/motd/description"),
+    ("favicon", "/motd/favicon"),
+    ("enforces_secure_chat", "/motd/enforcesSecureChat"),
+    ("online_mode", "/online_mode"),
+    ("login", "/login")
+];
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ResultDiff {
+    pub added: Vec<Target>,
+    pub removed: Vec<Target>,
+    pub changed: Vec<ChangedServer>
+}
+
+impl ResultDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.changed.len()
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ChangedServer {
+    #[serde(flatten)]
+    pub target: Target,
+    pub changes: BTreeMap<&'static str, Change>
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Change {
+    pub old: Value,
+    pub new: Value
+}
+
+/// Compares two directories of saved results by `ip:port`.
+pub fn diff_dirs(old: &Path, new: &Path) -> anyhow::Result<ResultDiff> {
+    Ok(diff_results(&input::saved_results(old)?, &input::saved_results(new)?))
+}
+
+pub fn diff_results(old: &BTreeMap<Target, Value>, new: &BTreeMap<Target, Value>) -> ResultDiff {
+    let mut diff = ResultDiff {
+        added: new.keys().filter(|target| !old.contains_key(target)).cloned().collect(),
+        removed: old.keys().filter(|target| !new.contains_key(target)).cloned().collect(),
+        ..Default::default()
+    };
+
+    for (target, old_result) in old {
+        let Some(new_result) = new.get(target) else { continue };
+
+        let changes: BTreeMap<_, _> = FIELDS.iter()
+            .filter_map(|&(name, pointer)| {
+                // missing and null are the same, older results skip empty fields
+                let old = old_result.pointer(pointer).cloned().unwrap_or_default();
+                let new = new_result.pointer(pointer).cloned().unwrap_or_default();
+                (old != new).then(|| (name, Change { old: shorten(name, old), new: shorten(name, new) }))
+            })
+            .collect();
+
+        if !changes.is_empty() {
+            diff.changed.push(ChangedServer { target: target.clone(), changes });
+        }
+    }
+
+    diff
+}
+
+/// A favicon is a few kilobytes of base64, a hash tells just as well that it changed.
+fn shorten(name: &str, value: Value) -> Value {
+    match (name, value) {
+        ("favicon", Value::String(favicon)) => {
+            let mut hasher = DefaultHasher::new();
+            favicon.hash(&mut hasher);
+            Value::String(format!("{:016x}", hasher.finish()))
+        },
+        (_, value) => value
+    }
+}
@@ -0,0 +1,37 @@
+//! Deciding which scan results are worth keeping.
+//!
+//! The filter used to be hard-coded to servers with exactly 50 max players,
+//! protocol 760 and a favicon. All of those are options now and none of them
+//! is set by default, so every server that answered is kept.
+//!
+//! Note that `require_favicon` in particular defaults to `false`: vanilla
+//! servers without a `server-icon.png` send no favicon at all, and the old
+//! behavior silently dropped every one of them. Pass `--require-favicon` to
+//! get it back.
+
+use regex::Regex;
+
+use crate::motd::MOTD;
+
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// Only keep servers announcing exactly this many player slots.
+    pub max_players: Option<u32>,
+    /// Only keep servers reporting this protocol version.
+    pub protocol: Option<i32>,
+    /// Only keep servers that sent a favicon.
+    pub require_favicon: bool,
+    /// Only keep servers whose description matches, tested against the
+    /// plain text with all components flattened and formatting removed.
+    pub motd_regex: Option<Regex>
+}
+
+impl Filter {
+    /// Every configured criterion has to match (AND), unset ones always do.
+    pub fn matches(&self, motd: &MOTD) -> bool {
+        self.max_players.is_none_or(|max| motd.players.max == max)
+            && self.protocol.is_none_or(|protocol| motd.version.protocol == protocol)
+            && (!self.require_favicon || motd.favicon.is_some())
+            && self.motd_regex.as_ref().is_none_or(|regex| regex.is_match(&motd.description.plain_text()))
+    }
+}
@@ -0,0 +1,57 @@
+use std::{collections::{hash_map::{DefaultHasher, Entry}, HashMap}, hash::{Hash, Hasher}, sync::Mutex};
+
+/// Spots hosts that answer many ports with the exact same status, which is
+/// what honeypots and tarpits do to waste scanners' time.
+///
+/// Results stream out as they complete, so only the response that crosses
+/// the threshold and the ones after it get flagged, earlier ports of the
+/// same host have already been handed out.
+///
+/// Every host that answered is remembered for the whole scan, as two
+/// hashes (around 40 bytes) until it answers a second time. Only those get
+/// the full per-status counts.
+pub struct HoneypotDetector {
+    threshold: usize,
+    seen: Mutex<Seen>
+}
+
+#[derive(Default)]
+struct Seen {
+    // ip hash -> status hash of hosts that answered once
+    first: HashMap<u64, u64>,
+    // ip hash -> status hash -> answers, for hosts that answered more than once
+    repeated: HashMap<u64, HashMap<u64, usize>>
+}
+
+impl HoneypotDetector {
+    /// `threshold` below 2 would flag every host, it is raised to 2.
+    pub fn new(threshold: usize) -> Self {
+        Self { threshold: threshold.max(2), seen: Mutex::default() }
+    }
+
+    /// Records a status response and returns whether the host now looks like a honeypot.
+    pub fn observe(&self, ip: &str, status_hash: u64) -> bool {
+        let mut hasher = DefaultHasher::new();
+        ip.hash(&mut hasher);
+        let ip = hasher.finish();
+
+        let mut seen = self.seen.lock().unwrap();
+        let Seen { first, repeated } = &mut *seen;
+
+        let counts = match repeated.entry(ip) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match first.entry(ip) {
+                // a single answer never crosses the threshold
+                Entry::Vacant(first) => {
+                    first.insert(status_hash);
+                    return false;
+                },
+                Entry::Occupied(first) => entry.insert(HashMap::from([(first.remove(), 1)]))
+            }
+        };
+
+        let count = counts.entry(status_hash).or_default();
+        *count += 1;
+        *count >= self.threshold
+    }
+}
@@ -0,0 +1,265 @@
+use std::{collections::{BTreeMap, HashMap, HashSet}, path::Path};
+
+use serde::{Serialize, Deserialize};
+
+use crate::{bedrock::BEDROCK_DEFAULT_PORT, scan::Target};
+
+#[derive(Serialize, Deserialize)]
+pub struct IPPortEntry {
+    pub port: u16,
+    pub proto: String,
+    pub status: String,
+    pub reason: String,
+    pub ttl: i16
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct IPEntry {
+    pub ip: String,
+    pub timestamp: String,
+    pub ports: Vec<IPPortEntry>
+}
+
+/// The masscan output formats we can read targets from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    /// Binary if the file starts with the masscan magic, JSON otherwise
+    #[default]
+    Auto,
+    /// `-oJ`
+    Json,
+    /// `-oB`, much smaller than JSON for big scans
+    MasscanBinary
+}
+
+/// Which order the targets are scanned in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TargetOrder {
+    /// Every port of a host, then the next host
+    #[default]
+    HostsFirst,
+    /// One port on every host, then the next port. Spreads the load over the
+    /// hosts and looks less like a port scan to each of them
+    PortsFirst
+}
+
+// every binary file starts with "masscan/1.1\ns:<start time>\n", padded to 99 bytes
+const MASSCAN_MAGIC: &[u8] = b"masscan/1.";
+
+/// Reads a masscan output file, see [`InputFormat`].
+pub fn read_entries(path: &Path, format: InputFormat) -> anyhow::Result<Vec<IPEntry>> {
+    let file = std::fs::read(path)?;
+
+    match format {
+        InputFormat::MasscanBinary => parse_masscan_binary(&file),
+        InputFormat::Auto if file.starts_with(MASSCAN_MAGIC) => parse_masscan_binary(&file),
+        InputFormat::Auto | InputFormat::Json => Ok(serde_json::from_slice(&file)?)
+    }
+}
+
+/// Parses masscan's binary output (`-oB`), keeping only open ports like
+/// the JSON output does.
+///
+/// The file is a list of `[type][length][data]` records, type and length
+/// are 7 bit big-endian varints. Banners and closed ports are skipped.
+pub fn parse_masscan_binary(mut data: &[u8]) -> anyhow::Result<Vec<IPEntry>> {
+    if !data.starts_with(MASSCAN_MAGIC) {
+        anyhow::bail!("not a masscan binary file");
+    }
+
+    let mut entries = vec![];
+    while !data.is_empty() {
+        let (kind, length) = match (masscan_varint(&mut data), masscan_varint(&mut data)) {
+            (Some(kind), Some(length)) => (kind, length),
+            _ => break // cut off, masscan was probably killed
+        };
+
+        if data.len() < length {
+            break;
+        }
+        let (record, rest) = data.split_at(length);
+        data = rest;
+
+        let entry = match kind {
+            1 => masscan_status(record, false), // open, always tcp
+            6 => masscan_status(record, true), // open, with ip protocol
+            10 => masscan_status6(record), // open, ipv6
+            _ => continue // header, closed ports, banners
+        };
+        entries.push(entry.ok_or_else(|| anyhow::anyhow!("truncated masscan record of type {}", kind))?);
+    }
+
+    Ok(entries)
+}
+
+fn masscan_varint(data: &mut &[u8]) -> Option<usize> {
+    let mut value = 0usize;
+    loop {
+        let (&byte, rest) = data.split_first()?;
+        *data = rest;
+        value = (value << 7) | (byte & 0x7f) as usize;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+/// ipv4 records: timestamp, ip, [ip protocol], port, reason, ttl.
+fn masscan_status(record: &[u8], has_proto: bool) -> Option<IPEntry> {
+    let be32 = |at: usize| Some(u32::from_be_bytes(record.get(at..at + 4)?.try_into().ok()?));
+
+    let timestamp = be32(0)?;
+    let ip = std::net::Ipv4Addr::from(be32(4)?);
+    let (proto, rest) = match has_proto {
+        true => (*record.get(8)?, record.get(9..)?),
+        false => (6, record.get(8..)?)
+    };
+
+    masscan_entry(timestamp, ip.to_string(), proto, rest)
+}
+
+/// ipv6 records: timestamp, ip protocol, port, reason, ttl, ip version, ip.
+fn masscan_status6(record: &[u8]) -> Option<IPEntry> {
+    let timestamp = u32::from_be_bytes(record.get(0..4)?.try_into().ok()?);
+    let ip: [u8; 16] = record.get(10..26)?.try_into().ok()?;
+
+    masscan_entry(timestamp, std::net::Ipv6Addr::from(ip).to_string(), *record.get(4)?, record.get(5..)?)
+}
+
+/// Builds the entry from the shared port, reason, ttl tail of a record.
+fn masscan_entry(timestamp: u32, ip: String, proto: u8, tail: &[u8]) -> Option<IPEntry> {
+    let port = u16::from_be_bytes(tail.get(0..2)?.try_into().ok()?);
+    let (reason, ttl) = (*tail.get(2)?, *tail.get(3)?);
+
+    Some(IPEntry {
+        ip,
+        timestamp: timestamp.to_string(),
+        ports: vec![IPPortEntry {
+            port,
+            proto: match proto { 17 => "udp", 132 => "sctp", _ => "tcp" }.to_string(),
+            status: "open".to_string(),
+            reason: tcp_flags(reason),
+            ttl: ttl as i16
+        }]
+    })
+}
+
+/// The reason is the tcp flags of the answer, masscan's JSON prints them
+/// like `syn-ack`.
+fn tcp_flags(flags: u8) -> String {
+    const NAMES: [&str; 8] = ["fin", "syn", "rst", "psh", "ack", "urg", "ece", "cwr"];
+
+    let names: Vec<&str> = NAMES.iter()
+        .enumerate()
+        .filter(|(bit, _)| flags & (1 << bit) != 0)
+        .map(|(_, name)| *name)
+        .collect();
+
+    if names.is_empty() { "none".to_string() } else { names.join("-") }
+}
+
+/// Reads the targets back out of a directory of saved results,
+/// sorted so rechecks run in a stable order.
+pub fn saved_targets(dir: &Path) -> anyhow::Result<Vec<Target>> {
+    Ok(saved_results(dir)?.into_keys().collect())
+}
+
+/// Reads a directory of saved results as plain JSON, so results written by
+/// older versions (`{ip}.json` instead of `{ip}_{port}.json`) still load.
+///
+/// Files that are not results (`index.json`, favicons, ...) are skipped.
+pub fn saved_results(dir: &Path) -> anyhow::Result<BTreeMap<Target, serde_json::Value>> {
+    let mut results = BTreeMap::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+
+        let result: serde_json::Value = match serde_json::from_str(&std::fs::read_to_string(&path)?) {
+            Ok(result) => result,
+            Err(_) => continue
+        };
+
+        // a result has ip and port at the top level, like a target
+        let Ok(target) = Target::deserialize(&result) else { continue };
+
+        // a recheck over old output leaves both files around, the newer one wins
+        let timestamp = |result: &serde_json::Value| result["timestamp"].as_u64().unwrap_or(0);
+        if results.get(&target).is_none_or(|saved| timestamp(saved) < timestamp(&result)) {
+            results.insert(target, result);
+        }
+    }
+
+    Ok(results)
+}
+
+/// Merges several target lists into one, dropping duplicates but keeping
+/// the order in which targets were first seen.
+pub fn merge_targets(lists: impl IntoIterator<Item = Vec<Target>>) -> Vec<Target> {
+    let mut seen = HashSet::new();
+
+    lists.into_iter()
+        .flatten()
+        .filter(|target| seen.insert(target.clone()))
+        .collect()
+}
+
+/// Expands masscan entries into one target per open port.
+pub fn targets(entries: &[IPEntry]) -> Vec<Target> {
+    entries.iter()
+        .flat_map(|entry|
+            entry.ports.iter().map(|port| Target { ip: entry.ip.clone(), port: port.port })
+        )
+        .collect()
+}
+
+/// Puts the targets into `order`, hosts and ports keep the order they were
+/// first seen in.
+pub fn order_targets(targets: Vec<Target>, order: TargetOrder) -> Vec<Target> {
+    let key = |target: &Target| match order {
+        TargetOrder::HostsFirst => target.ip.clone(),
+        TargetOrder::PortsFirst => target.port.to_string()
+    };
+
+    let mut groups: Vec<Vec<Target>> = vec![];
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for target in targets {
+        let group = *index.entry(key(&target)).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[group].push(target);
+    }
+
+    groups.into_iter().flatten().collect()
+}
+
+/// The ttl masscan saw for each target, `scan_batch` copies it into the results.
+pub fn ttls(entries: &[IPEntry]) -> HashMap<Target, i16> {
+    entries.iter()
+        .flat_map(|entry|
+            entry.ports.iter().map(|port| (Target { ip: entry.ip.clone(), port: port.port }, port.ttl))
+        )
+        .collect()
+}
+
+/// Bedrock servers listen on UDP, so only udp ports from the scan are used.
+/// Hosts without any get probed on the default Bedrock port instead.
+pub fn bedrock_targets(entries: &[IPEntry]) -> Vec<Target> {
+    entries.iter()
+        .flat_map(|entry| {
+            let mut ports: Vec<u16> = entry.ports.iter()
+                .filter(|port| port.proto == "udp")
+                .map(|port| port.port)
+                .collect();
+
+            if ports.is_empty() {
+                ports.push(BEDROCK_DEFAULT_PORT);
+            }
+
+            ports.into_iter().map(|port| Target { ip: entry.ip.clone(), port })
+        })
+        .collect()
+}
@@ -0,0 +1,112 @@
+use std::time::Instant;
+
+use bytes::{ BufMut, BytesMut };
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, time::timeout};
+
+use crate::{
+    motd::{MOTDDescription, MOTDPlayers, MOTDVersion, MOTD},
+    protocol::MAX_HANDSHAKE_HOST,
+    scan::{ScanConfig, ScanError, ScanResult, Target},
+    transport::{self, Transport}
+};
+
+const PING: u8 = 0xfe;
+const KICK: u8 = 0xff;
+
+// what 1.6 clients put into their ping, older servers stop reading after 0xfe 0x01
+const PING_HOST_PROTOCOL: u8 = 74;
+
+/// Pings a server from before 1.7, which does not understand the handshake.
+///
+/// Servers answer with a kick packet carrying their status as a string, in
+/// one of two shapes:
+/// - 1.4 to 1.6: `§1\0protocol\0version\0motd\0online\0max`
+/// - beta 1.8 to 1.3: `motd§online§max`, without any version
+pub async fn legacy_ping<T: Transport>(target: &Target, config: &ScanConfig) -> Result<ScanResult, ScanError> {
+    let mut stream = T::connect(target, config).await?;
+
+    let (host, port) = config.handshake_addr(target);
+    stream.write_all(&ping_request(host, port)).await?;
+
+    let sent = Instant::now();
+    let mut header = [0u8; 3];
+    read_exact(&mut stream, &mut header, config).await?;
+    if header[0] != KICK {
+        return Err(ScanError::MalformedResponse);
+    }
+
+    // the length counts UTF-16 units, not bytes
+    let mut response = vec![0u8; u16::from_be_bytes([header[1], header[2]]) as usize * 2];
+    read_exact(&mut stream, &mut response, config).await?;
+    let latency = sent.elapsed();
+
+    transport::close(&mut stream, config).await;
+
+    let motd = parse_legacy_response(&response)?;
+    let matched = config.filter.matches(&motd);
+
+    let mut result = ScanResult::new(target, motd, matched, latency);
+    result.bytes_read = (header.len() + response.len()) as u64;
+    result.legacy = true;
+    Ok(result)
+}
+
+/// Parses the UTF-16BE string of a legacy kick packet (without the packet
+/// id and length).
+pub fn parse_legacy_response(response: &[u8]) -> Result<MOTD, ScanError> {
+    let units: Vec<u16> = response.chunks_exact(2).map(|unit| u16::from_be_bytes([unit[0], unit[1]])).collect();
+    let response = String::from_utf16(&units).map_err(|_| ScanError::MalformedResponse)?;
+
+    let (protocol, version, motd, online, max) = match response.strip_prefix("§1\0") {
+        Some(fields) => match fields.split('\0').collect::<Vec<_>>()[..] {
+            [protocol, version, motd, online, max] => (protocol.parse().ok(), version, motd, online, max),
+            _ => return Err(ScanError::MalformedResponse)
+        },
+        // the motd itself cant contain §, so the last two are always the counts
+        None => match response.rsplitn(3, '§').collect::<Vec<_>>()[..] {
+            [max, online, motd] => (Some(0), "", motd, online, max),
+            _ => return Err(ScanError::MalformedResponse)
+        }
+    };
+
+    Ok(MOTD {
+        description: MOTDDescription::Text(motd.to_string()),
+        players: MOTDPlayers {
+            max: max.parse().map_err(|_| ScanError::MalformedResponse)?,
+            online: online.parse().map_err(|_| ScanError::MalformedResponse)?,
+            sample: None
+        },
+        version: MOTDVersion {
+            name: version.to_string(),
+            protocol: protocol.ok_or(ScanError::MalformedResponse)?
+        },
+        favicon: None,
+        enforces_secure_chat: None,
+        previews_chat: None
+    })
+}
+
+/// The 1.6 ping, 1.4 and 1.5 answer just the first two bytes of it and
+/// older servers only the first.
+fn ping_request(host: &str, port: u16) -> BytesMut {
+    let channel: Vec<u16> = "MC|PingHost".encode_utf16().collect();
+    let host: Vec<u16> = host.chars().take(MAX_HANDSHAKE_HOST).collect::<String>().encode_utf16().collect();
+
+    let mut packet = BytesMut::new();
+    packet.put_u8(PING);
+    packet.put_u8(0x01); // ask for the §1 format
+    packet.put_u8(0xfa); // plugin message
+    packet.put_u16(channel.len() as u16);
+    channel.iter().for_each(|&unit| packet.put_u16(unit));
+    packet.put_u16(7 + 2 * host.len() as u16); // length of the rest
+    packet.put_u8(PING_HOST_PROTOCOL);
+    packet.put_u16(host.len() as u16);
+    host.iter().for_each(|&unit| packet.put_u16(unit));
+    packet.put_i32(port as i32);
+    packet
+}
+
+async fn read_exact(stream: &mut impl Transport, buf: &mut [u8], config: &ScanConfig) -> Result<(), ScanError> {
+    timeout(config.read_timeout, stream.read_exact(buf)).await.map_err(|_| ScanError::Timeout)??;
+    Ok(())
+}
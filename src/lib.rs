@@ -0,0 +1,23 @@
+pub mod anonymize;
+pub mod bedrock;
+pub mod checkpoint;
+pub mod cooldown;
+pub mod diff;
+pub mod filter;
+pub mod honeypot;
+pub mod input;
+pub mod legacy;
+pub mod login;
+pub mod preflight;
+pub mod metrics;
+pub mod motd;
+pub mod output;
+pub mod protocol;
+pub mod query;
+pub mod rate;
+pub mod resolve;
+pub mod scan;
+pub mod status;
+pub mod transport;
+
+pub use scan::{estimate_hops, perform_scan, perform_scan_with, scan_outcomes, scan_stream, ResultHook, ScanConfig, ScanError, ScanResult, Target, JAVA_DEFAULT_PORT, MAX_PACKET_SIZE, MAX_PROTOCOL_CANDIDATES, SCHEMA_VERSION};
@@ -0,0 +1,229 @@
+use bytes::{ Buf, BufMut, Bytes, BytesMut };
+use serde::{Serialize, Deserialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    protocol::{build_handshake, BufExt, BytesMutExt, FramedReader, Packet, STATE_LOGIN},
+    scan::{ScanConfig, ScanError, Target},
+    transport::{self, Transport}
+};
+
+// Login Start changed shape a few times, these are the protocols where it did
+const PROTOCOL_1_19: i32 = 759; // signature data added
+const PROTOCOL_1_19_1: i32 = 760; // optional uuid added
+const PROTOCOL_1_19_3: i32 = 761; // signature data removed again
+const PROTOCOL_1_20_2: i32 = 764; // uuid mandatory, configuration phase added
+const PROTOCOL_1_20_5: i32 = 766; // encryption request gained "should authenticate"
+
+const USERNAME: &str = "quickie";
+
+// Login frames are tiny, anything bigger is not a login response
+const MAX_LOGIN_FRAME: usize = 0xFFFF;
+
+/// The phase the server moves the connection into after Login Success.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginPhase {
+    /// Before 1.20.2 login goes straight to play.
+    Play,
+    /// 1.20.2+ goes through configuration after we acknowledge the login.
+    Configuration
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind", content = "reason")]
+pub enum PostLogin {
+    /// The server started sending packets of the next phase.
+    Accepted,
+    /// The server kicked us right after Login Success (auth plugins, whitelists
+    /// enforced late, ...). The reason is only kept when it was sent as JSON.
+    Disconnected(Option<String>)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LoginProbe {
+    /// The protocol we logged in with.
+    pub protocol: i32,
+    /// `None` when the server kicked us before revealing it.
+    pub online_mode: Option<bool>,
+    pub disconnect_reason: Option<String>,
+    pub next_phase: Option<LoginPhase>,
+    pub post_login: Option<PostLogin>
+}
+
+impl LoginProbe {
+    fn new(protocol: i32) -> Self {
+        Self { protocol, online_mode: None, disconnect_reason: None, next_phase: None, post_login: None }
+    }
+}
+
+/// Tries to log in as an offline player to find out whether the server is in
+/// online mode.
+///
+/// An Encryption Request means the server wants to authenticate us with
+/// Mojang, Set Compression or Login Success without one means it does not.
+/// `protocol` should be the one the server reported in its status, the
+/// Login Start layout depends on it.
+pub async fn probe_login<T: Transport>(target: &Target, protocol: i32, config: &ScanConfig) -> Result<LoginProbe, ScanError> {
+    let stream = T::connect(target, config).await?;
+    let mut stream = FramedReader::new(stream, config.read_timeout);
+
+    let (handshake_host, handshake_port) = config.handshake_addr(target);
+    let handshake = build_handshake(protocol, handshake_host, handshake_port, STATE_LOGIN);
+    stream.get_mut().write_all(&handshake).await?;
+    stream.get_mut().write_all(&login_start(protocol)).await?;
+
+    let mut probe = LoginProbe::new(protocol);
+    let mut compressed = false;
+
+    loop {
+        let Packet { id, data: mut packet } = match read_packet(&mut stream, compressed).await? {
+            Some(packet) => packet,
+            None => break // compressed beyond the threshold, we cant look inside
+        };
+
+        match id {
+            0x00 => { // disconnect
+                probe.disconnect_reason = packet.get_str();
+                break;
+            },
+            0x01 => { // encryption request
+                probe.online_mode = Some(encryption_authenticates(protocol, &mut packet));
+                break;
+            },
+            0x02 => { // login success
+                probe.online_mode.get_or_insert(false);
+                probe.next_phase = Some(if protocol >= PROTOCOL_1_20_2 {
+                    LoginPhase::Configuration
+                } else {
+                    LoginPhase::Play
+                });
+                probe.post_login = post_login(&mut stream, protocol, compressed).await;
+                break;
+            },
+            0x03 => { // set compression
+                // vanilla only enables compression after encryption, so this means offline mode
+                probe.online_mode = Some(false);
+                compressed = true;
+            },
+            0x04 => { // login plugin request (velocity forwarding and friends)
+                let message_id = packet.get_vi().ok_or(ScanError::MalformedResponse)?;
+
+                let mut response = BytesMut::new();
+                response.put_vi(message_id);
+                response.put_u8(0); // we dont understand the channel
+
+                stream.get_mut().write_all(&encode_outgoing(0x02, &response, compressed)).await?;
+            },
+            id => return Err(ScanError::UnexpectedPacket(id))
+        }
+    }
+
+    transport::close(stream.get_mut(), config).await; // the server may already be gone
+
+    Ok(probe)
+}
+
+fn login_start(protocol: i32) -> BytesMut {
+    let mut data = BytesMut::new();
+    data.put_str(USERNAME);
+
+    match protocol {
+        PROTOCOL_1_19 => data.put_u8(0), // no signature data
+        PROTOCOL_1_19_1 => {
+            data.put_u8(0); // no signature data
+            data.put_u8(0); // no uuid
+        },
+        PROTOCOL_1_19_3..=763 => data.put_u8(0), // no uuid
+        p if p >= PROTOCOL_1_20_2 => data.put_u128(0), // uuid, the server assigns its own in offline mode
+        _ => {} // just the name
+    }
+
+    Packet::new(0x00, data).encode()
+}
+
+/// 1.20.5+ servers behind a proxy can ask for encryption without
+/// authenticating, older ones always authenticate when they encrypt.
+fn encryption_authenticates(protocol: i32, packet: &mut Bytes) -> bool {
+    if protocol < PROTOCOL_1_20_5 {
+        return true;
+    }
+
+    let skip_array = |packet: &mut Bytes| -> Option<()> {
+        let len = packet.get_vi()? as usize;
+        (packet.remaining() >= len).then(|| packet.advance(len))
+    };
+
+    let should_authenticate = (|| {
+        packet.get_str()?; // server id
+        skip_array(packet)?; // public key
+        skip_array(packet)?; // verify token
+        packet.has_remaining().then(|| packet.get_u8() != 0)
+    })();
+
+    should_authenticate.unwrap_or(true)
+}
+
+/// Looks at what the server sends once it accepted the login.
+///
+/// 1.20.2+ waits for Login Acknowledged before moving to configuration, so we
+/// send it, older servers go straight to play on their own. Either way a kick
+/// at this point is still an offline-mode server, but worth recording.
+async fn post_login(stream: &mut FramedReader<impl Transport>, protocol: i32, compressed: bool) -> Option<PostLogin> {
+    if protocol >= PROTOCOL_1_20_2 {
+        stream.get_mut().write_all(&encode_outgoing(0x03, &[], compressed)).await.ok()?; // login acknowledged
+    }
+
+    let Packet { id, data: mut packet } = match read_packet(stream, compressed).await.ok()? {
+        Some(packet) => packet,
+        None => return Some(PostLogin::Accepted) // big compressed packets are never kicks
+    };
+
+    if protocol >= PROTOCOL_1_20_2 {
+        // configuration disconnect moved from 0x01 to 0x02 in 1.20.5
+        let disconnect = if protocol >= PROTOCOL_1_20_5 { 0x02 } else { 0x01 };
+        if id != disconnect {
+            return Some(PostLogin::Accepted);
+        }
+
+        // 1.20.3+ sends the reason as NBT which we dont decode
+        let reason = packet.get_str().filter(|reason| serde_json::from_str::<serde_json::Value>(reason).is_ok());
+        return Some(PostLogin::Disconnected(reason));
+    }
+
+    // The play disconnect id moves around between every release, but its
+    // payload is always a JSON chat string, which Join Game never starts with.
+    match packet.get_str() {
+        Some(reason) if serde_json::from_str::<serde_json::Value>(&reason).is_ok() => Some(PostLogin::Disconnected(Some(reason))),
+        _ => Some(PostLogin::Accepted)
+    }
+}
+
+/// Reads the next frame, unwrapping the compression header if enabled.
+/// Returns `None` for compressed payloads since we dont inflate them.
+async fn read_packet(stream: &mut FramedReader<impl Transport>, compressed: bool) -> Result<Option<Packet>, ScanError> {
+    let mut frame = stream.read_frame(MAX_LOGIN_FRAME).await?;
+
+    if compressed && frame.get_vi().ok_or(ScanError::MalformedResponse)? != 0 {
+        return Ok(None);
+    }
+
+    Packet::from_frame(frame).map(Some)
+}
+
+fn encode_outgoing(id: i32, data: &[u8], compressed: bool) -> BytesMut {
+    if !compressed {
+        return Packet::new(id, data.to_vec()).encode();
+    }
+
+    // below the threshold, so send it with a data length of 0 (uncompressed)
+    let mut inner = BytesMut::new();
+    inner.put_vi(0);
+    inner.put_varint_i32(id);
+    inner.put(data);
+
+    let mut packet = BytesMut::new();
+    packet.put_vi(inner.len() as u32);
+    packet.put(inner);
+    packet
+}
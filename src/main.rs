@@ -1,177 +1,590 @@
-use std::time::Duration;
-
-use bytes::{ BytesMut, BufMut, Buf };
-use tokio::{io::{AsyncWriteExt, AsyncReadExt}, net::TcpStream, time::timeout};
-use tracing::info;
-
-use serde::{Serialize, Deserialize};
-use unsigned_varint::decode::is_last;
-
-#[derive(Serialize, Deserialize)]
-struct IPPortEntry {
-    port: u16,
-    proto: String,
-    status: String,
-    reason: String,
-    ttl: i16
-}
+use std::{collections::HashMap, net::SocketAddr, path::{Path, PathBuf}, process::ExitCode, sync::{atomic::{AtomicU64, Ordering}, Arc}, time::Duration};
 
-#[derive(Serialize, Deserialize)]
-struct IPEntry {
-    ip: String,
-    timestamp: String,
-    ports: Vec<IPPortEntry>
-}
+use anyhow::Context;
 
-#[derive(Serialize, Deserialize)]
-struct MOTDDescription {
-    text: String
-}
+use clap::{Parser, Subcommand, ValueEnum};
+use futures::StreamExt;
+use regex::Regex;
+use tracing::{error, info, warn, Level};
 
-#[derive(Serialize, Deserialize)]
-struct MOTDPlayers {
-    max: u32,
-    online: u32,
-    sample: Option<Vec<MOTDPlayer>>
-}
+use quickie::{anonymize, bedrock::{bedrock_scan, BEDROCK_DEFAULT_PORT}, checkpoint::{self, Checkpoint, Outcome}, filter::Filter, input::{self, InputFormat, TargetOrder}, metrics, output::{self, ConsolidatedSink, FaviconFormat, FileSink, OutputConfig, OutputSink, ResultWriter, SortBy, SqliteSink}, perform_scan, preflight::preflight, resolve::Resolver, scan_outcomes, ScanConfig, ScanError, Target, JAVA_DEFAULT_PORT, MAX_PACKET_SIZE, MAX_PROTOCOL_CANDIDATES};
 
-#[derive(Serialize, Deserialize)]
-struct MOTDPlayer {
-    name: String,
-    id: String
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Edition {
+    Java,
+    /// RakNet unconnected ping over UDP, defaults to port 19132
+    Bedrock
 }
 
-#[derive(Serialize, Deserialize)]
-struct MOTDVersion {
-    name: String,
-    protocol: u32
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputMode {
+    /// A JSON file and favicon per ip and port plus an index.json
+    Files,
+    /// A single results.json, kept in memory until the scan is done
+    Consolidated,
+    /// A SQLite database at --db-path
+    Sqlite
 }
 
-#[derive(Serialize, Deserialize)]
-struct MOTD {
-    description: MOTDDescription,
-    players: MOTDPlayers,
-    version: MOTDVersion,
-    favicon: Option<String>
+#[derive(Subcommand)]
+enum Command {
+    /// Scan a single `host[:port]` and print its status as JSON
+    Scan {
+        target: String
+    },
+    /// Scan the servers saved in a result directory again, updating their
+    /// files and listing the ones that went offline in offline.json
+    Recheck {
+        dir: PathBuf
+    },
+    /// Compare two result directories and print the added, removed and
+    /// changed servers as JSON
+    #[command(after_help = "Exits with 0 when the directories hold the same servers, 1 when they differ and 2 on errors.")]
+    Diff {
+        old: PathBuf,
+        new: PathBuf
+    }
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt::init();
+#[derive(Parser)]
+#[command(version, about, after_help = "Exits with 0 when at least one server matched, 1 when none did and 2 on errors. `diff` exits with 0 when nothing changed and 1 otherwise.")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    let file = include_str!("../out.json");
-    let entries: Vec<IPEntry> = serde_json::from_str(file)?;
+    /// masscan output (JSON or binary) to read targets from, can be given multiple times
+    #[arg(long, default_value = "out.json")]
+    input: Vec<PathBuf>,
 
-    let mut futures = vec![];
+    /// Format of the --input files
+    #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+    format: InputFormat,
 
-    entries.iter()
-        .for_each(|entry|
-            entry.ports.iter().for_each(|port|
-                futures.push(async { perform_scan(&entry.ip, port.port).await.unwrap() })
-            )
-        );
+    /// Order the targets are scanned in
+    #[arg(long, value_enum, default_value_t = TargetOrder::HostsFirst)]
+    order: TargetOrder,
 
-    #[cfg(debug_assertions)]
-    futures.truncate(1);
+    /// Record the outcome of every attempted target in this file (JSON lines)
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
 
-    tokio::fs::create_dir("data").await.unwrap_or_default(); // We dont care about the error
+    /// Skip targets already recorded in the checkpoint
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
 
-    futures::future::join_all(futures).await;
+    /// When resuming, try transient failures (timeouts, resets) again
+    #[arg(long, requires = "resume")]
+    retry_failures: bool,
 
-    Ok(())
-}
+    /// Which edition's protocol to scan with
+    #[arg(long, value_enum, default_value_t = Edition::Java)]
+    edition: Edition,
 
-async fn perform_scan(ip: &str, port: u16) -> anyhow::Result<()> {
-    info!("Scanning {}:{}", ip, port);
+    /// Resolve all hosts and try a few targets before the scan, aborting if none are reachable
+    #[arg(long)]
+    preflight: bool,
 
-    let mut stream = TcpStream::connect((ip, port)).await?;
+    /// How many targets the preflight tries to connect to
+    #[arg(long, default_value_t = 10)]
+    preflight_sample: usize,
 
-    // lets build a handshake packet
-    let mut handshake_data = BytesMut::new();
-    handshake_data.put_vi(760); // protocol version
-    handshake_data.put_str(ip); // ip
-    handshake_data.put_u16(port); // port
-    handshake_data.put_vi(1); // state
+    /// Only keep servers with exactly this many player slots
+    #[arg(long)]
+    max_players: Option<u32>,
 
-    let mut handshake = BytesMut::new();
-    handshake.put_vi(handshake_data.len() as u32 + 1); // packet length
-    handshake.put_vi(0x00); // packet id (0x00 = handshake)
-    handshake.put(handshake_data); // packet data
+    /// Only keep servers reporting this protocol version
+    #[arg(long)]
+    filter_protocol: Option<i32>,
 
-    // Send the handsake packet
-    stream.write(&handshake).await?;
+    /// Only keep servers that send a favicon (this used to be hard-coded)
+    #[arg(long)]
+    require_favicon: bool,
 
-    let mut request = BytesMut::new();
-    request.put_vi(1); // packet length
-    request.put_vi(0x00); // packet id (0x00 = handshake)
+    /// Only keep servers whose description (as plain text) matches this regex
+    #[arg(long, value_parser = Regex::new)]
+    motd_regex: Option<Regex>,
 
-    // Send the request packet
-    stream.write(&request).await?;
+    /// Where matching results are written to
+    #[arg(long, value_enum, default_value_t = OutputMode::Files)]
+    output: OutputMode,
 
-    // Read the response
-    let mut response = BytesMut::with_capacity(0xFFFFF); // 1MB
-    let mut buffer = [0u8; 0xFF]; // 255 bytes
+    /// Database the sqlite output writes to, updated when it already exists
+    #[arg(long, default_value = "results.db")]
+    db_path: PathBuf,
 
-    while let Ok(result) = timeout(Duration::from_millis(500), stream.read(&mut buffer)).await {
-        match result {
-            Ok(len) => {
-                if len == 0 {
-                    break;
-                }
-    
-                response.put(&buffer[..len]);
-            },
-            Err(_) => { // timeout
-                break;
-            }
+    /// Sort the consolidated results by this field
+    #[arg(long, value_enum)]
+    sort_by: Option<SortBy>,
+
+    /// Group the consolidated results by the --sort-by field instead of only sorting
+    #[arg(long, requires = "sort_by")]
+    group: bool,
+
+    /// Only save the favicons of matching servers, no result JSON (implies --require-favicon)
+    #[arg(long)]
+    favicon_only: bool,
+
+    /// Convert the saved favicons, webp is lossless and smaller, jpeg loses the transparency
+    #[arg(long, value_enum, default_value = "png")]
+    favicon_format: FaviconFormat,
+
+    /// Pretty-print the JSON output (default for per-file output)
+    #[arg(long, conflicts_with = "compact")]
+    pretty: bool,
+
+    /// Write compact JSON, smaller and faster to parse
+    #[arg(long)]
+    compact: bool,
+
+    /// Stop writing result files after this many, the scan keeps going (per-file output)
+    #[arg(long)]
+    max_output_files: Option<u64>,
+
+    /// Stop writing result files once they take up this many bytes (per-file output)
+    #[arg(long)]
+    max_output_bytes: Option<u64>,
+
+    /// How many result files are written at the same time
+    #[arg(long, default_value_t = 16)]
+    write_concurrency: usize,
+
+    /// Consecutive connection failures before a host's other ports are skipped (0 disables)
+    #[arg(long, default_value_t = 5)]
+    host_failure_threshold: u32,
+
+    /// Seconds a failing host is skipped for, doubling every time it happens again
+    #[arg(long, default_value_t = 30)]
+    host_cooldown: u64,
+
+    /// Start at most this many scans per second
+    #[arg(long)]
+    rate: Option<f64>,
+
+    /// Adapt --rate to keep the share of scans timing out or getting reset around this
+    /// (e.g. 0.05), speeding up while it stays below and backing off when it spikes
+    #[arg(long, requires = "rate", value_name = "TARGET_ERROR_RATE")]
+    adaptive_rate: Option<f64>,
+
+    /// Maximum random delay in milliseconds before each connect, smooths out bursts
+    #[arg(long, default_value_t = 0)]
+    connect_jitter: u64,
+
+    /// Seed for the connect jitter, random if not given
+    #[arg(long)]
+    jitter_seed: Option<u64>,
+
+    /// Also ask matching servers for their full stat over the UDP query protocol
+    #[arg(long)]
+    query: bool,
+
+    /// Close connections with a reset so they skip TIME_WAIT. Aggressive, only
+    /// for huge scans running out of local ports
+    #[arg(long)]
+    reset_on_close: bool,
+
+    /// Largest status response in bytes to accept
+    #[arg(long, default_value_t = MAX_PACKET_SIZE)]
+    max_packet_size: usize,
+
+    /// Give up on a target after this many seconds in total, whatever the server is sending
+    #[arg(long, default_value_t = 10)]
+    scan_deadline: u64,
+
+    /// Measure the status ping round trip of matching servers
+    #[arg(long)]
+    ping: bool,
+
+    /// Fall back to the pre-1.7 ping for servers that dont answer the status
+    #[arg(long)]
+    legacy_fallback: bool,
+
+    /// Scan again with the server's own protocol version when it reports a different one
+    #[arg(long)]
+    auto_version: bool,
+
+    /// Scan every target announcing each of these protocols (comma separated, at most 4)
+    /// and keep the most complete response
+    #[arg(long, value_delimiter = ',')]
+    protocol_candidates: Vec<i32>,
+
+    /// Retry status responses that are not valid UTF-8 with the bad bytes replaced
+    #[arg(long)]
+    lossy_utf8: bool,
+
+    /// Resolve hostnames with this DNS-over-HTTPS endpoint instead of the system resolver
+    /// (e.g. https://1.1.1.1/dns-query, an ip endpoint does not need the system resolver itself)
+    #[arg(long)]
+    doh: Option<String>,
+
+    /// Replace ips with a hash keyed with this secret and strip the motd text, favicon,
+    /// player names and query, for publishing statistics
+    #[arg(long, value_name = "SALT")]
+    anonymize: Option<String>,
+
+    /// Tag every result with this campaign, to tell scans apart when merging their results
+    #[arg(long)]
+    label: Option<String>,
+
+    /// Server address to send in the handshake instead of the target ip
+    #[arg(long)]
+    handshake_host: Option<String>,
+
+    /// Port to send in the handshake instead of the one we connect to
+    #[arg(long)]
+    handshake_port: Option<u16>,
+
+    /// Worker threads of the async runtime, defaults to one per CPU core
+    #[arg(long, conflicts_with = "current_thread")]
+    worker_threads: Option<usize>,
+
+    /// Run everything on a single thread
+    #[arg(long)]
+    current_thread: bool,
+
+    /// Print a histogram of the status latencies once the scan is done, for tuning the timeouts
+    #[arg(long)]
+    timeout_histogram: bool,
+
+    /// Serve Prometheus metrics on this address (e.g. 127.0.0.1:9100) while scanning
+    #[arg(long)]
+    metrics_addr: Option<SocketAddr>,
+
+    /// Mark a host as a likely honeypot once this many (at least 2) of its ports return an identical status
+    #[arg(long, value_parser = clap::value_parser!(u64).range(2..))]
+    honeypot_threshold: Option<u64>,
+
+    /// Only log errors
+    #[arg(long, short)]
+    quiet: bool
+}
+
+// exit codes, so scripts can tell "nothing found" from "something broke"
+const EXIT_MATCHES: u8 = 0;
+const EXIT_NO_MATCHES: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    // logs go to stderr so stdout stays clean for results
+    let level = if args.quiet { Level::ERROR } else { Level::INFO };
+    tracing_subscriber::fmt().with_writer(std::io::stderr).with_max_level(level).init();
+
+    match start(args) {
+        Ok(0) => ExitCode::from(EXIT_NO_MATCHES),
+        Ok(_) => ExitCode::from(EXIT_MATCHES),
+        Err(err) => {
+            error!("{:#}", err);
+            ExitCode::from(EXIT_ERROR)
         }
     }
+}
+
+/// Runs the command and returns how many servers matched.
+fn start(args: Args) -> anyhow::Result<u64> {
+    // Scanning is almost all waiting on sockets, so a few threads keep
+    // thousands of connections busy and more cores mostly add contention. A
+    // single thread is enough for small scans and the cheapest to run next
+    // to other work, more threads only pay off once parsing (large favicons,
+    // --motd-regex) and writing output start to take real CPU time.
+    let mut runtime = if args.current_thread {
+        tokio::runtime::Builder::new_current_thread()
+    } else {
+        tokio::runtime::Builder::new_multi_thread()
+    };
+    if let Some(threads) = args.worker_threads {
+        runtime.worker_threads(threads.max(1));
+    }
+
+    runtime.enable_all().build()?.block_on(run(args))
+}
 
-    let mut response = response.freeze();
+async fn run(args: Args) -> anyhow::Result<u64> {
+    if args.protocol_candidates.len() > MAX_PROTOCOL_CANDIDATES {
+        warn!("Only the first {} of the --protocol-candidates are tried", MAX_PROTOCOL_CANDIDATES);
+    }
 
-    // lets strip away the packet length and string length, we dont need them
-    let mut count = 0;
+    let config = ScanConfig {
+        host_failure_threshold: args.host_failure_threshold,
+        host_cooldown: Duration::from_secs(args.host_cooldown),
+        connect_jitter: Duration::from_millis(args.connect_jitter),
+        rate: args.rate,
+        adaptive_rate: args.adaptive_rate,
+        jitter_seed: args.jitter_seed.unwrap_or_else(rand::random),
+        honeypot_threshold: args.honeypot_threshold.map(|threshold| threshold as usize),
+        lossy_utf8: args.lossy_utf8,
+        filter: Filter {
+            max_players: args.max_players,
+            protocol: args.filter_protocol,
+            require_favicon: args.require_favicon || args.favicon_only,
+            motd_regex: args.motd_regex.clone()
+        },
+        ping: args.ping,
+        query: args.query,
+        auto_version: args.auto_version,
+        protocol_candidates: args.protocol_candidates.clone(),
+        legacy_fallback: args.legacy_fallback,
+        handshake_host: args.handshake_host.clone(),
+        handshake_port: args.handshake_port,
+        label: args.label.clone(),
+        reset_on_close: args.reset_on_close,
+        max_packet_size: args.max_packet_size,
+        per_scan_deadline: Duration::from_secs(args.scan_deadline),
+        resolver: Arc::new(args.doh.clone().map_or_else(Resolver::system, Resolver::doh)),
+        ..Default::default()
+    };
+
+    match &args.command {
+        Some(Command::Scan { target }) => scan_single(target, &args, &config).await,
+        Some(Command::Recheck { dir }) => recheck(dir, &args, config).await,
+        Some(Command::Diff { old, new }) => diff(old, new, &args),
+        None => scan_batch(&args, config).await
+    }
+}
 
-    while count < 3 {
-        while !is_last(response[count]) {
-            response.advance(1);
+async fn scan_single(target: &str, args: &Args, config: &ScanConfig) -> anyhow::Result<u64> {
+    let (json, matched) = match args.edition {
+        Edition::Java => {
+            let target = Target::parse(target, JAVA_DEFAULT_PORT).map_err(anyhow::Error::msg)?;
+            let result = perform_scan(&target, config).await?;
+            (serde_json::to_string_pretty(&result.motd)?, result.matched)
+        },
+        Edition::Bedrock => {
+            let target = Target::parse(target, BEDROCK_DEFAULT_PORT).map_err(anyhow::Error::msg)?;
+            (serde_json::to_string_pretty(&bedrock_scan(&target, config).await?)?, true)
         }
+    };
+
+    println!("{}", json);
 
-        response.advance(1);
-        count += 1;
+    Ok(matched as u64)
+}
+
+fn diff(old: &Path, new: &Path, args: &Args) -> anyhow::Result<u64> {
+    let diff = quickie::diff::diff_dirs(old, new)?;
+    info!("{} added, {} removed, {} changed", diff.added.len(), diff.removed.len(), diff.changed.len());
+
+    match args.pretty || !args.compact {
+        true => println!("{}", serde_json::to_string_pretty(&diff)?),
+        false => println!("{}", serde_json::to_string(&diff)?)
     }
 
-    stream.shutdown().await?; // shutdown so we dont have to wait for too long
+    // exits like diff(1), 0 for identical directories and 1 for differences
+    Ok(diff.is_empty() as u64)
+}
+
+/// Counts the servers that are still up as matches.
+async fn recheck(dir: &Path, args: &Args, config: ScanConfig) -> anyhow::Result<u64> {
+    let targets = input::saved_targets(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    info!("Rechecking {} saved servers", targets.len());
+
+    let output = Arc::new(OutputConfig {
+        dir: dir.to_path_buf(),
+        write_concurrency: args.write_concurrency,
+        pretty: args.pretty || !args.compact,
+        ..Default::default()
+    });
+    let writer = ResultWriter::spawn(FileSink::new(output.clone()), output.write_concurrency);
+    let up = AtomicU64::new(0);
+
+    // every saved port gets a real answer, a host cooling down would leave
+    // its remaining ports neither up nor offline
+    let config = ScanConfig { host_failure_threshold: 0, ..config };
+
+    let offline: Vec<Target> = scan_outcomes(targets, config)
+        .filter_map(|(target, result)| {
+            let (writer, up) = (&writer, &up);
+            async move {
+                match result {
+                    // still up, refresh the saved result
+                    Ok(result) => {
+                        up.fetch_add(1, Ordering::Relaxed);
+                        writer.send(result).await;
+                        None
+                    },
+                    Err(err) if err.is_connection_failure() || matches!(err, ScanError::Timeout | ScanError::ScanTimeout(_)) => {
+                        warn!("{}:{} is offline: {}", target.ip, target.port, err);
+                        writer.sink().mark_offline(&target);
+                        Some(target)
+                    },
+                    Err(err) => {
+                        warn!("{}:{} answered but could not be scanned: {}", target.ip, target.port, err);
+                        None
+                    }
+                }
+            }
+        })
+        .collect()
+        .await;
+
+    writer.finish().await?;
 
-    let response: MOTD = serde_json::from_slice(&response)?;
+    info!("{} servers went offline", offline.len());
+    std::fs::write(dir.join("offline.json"), output.to_json(&offline)?)?;
 
-    // Filter out some servers we dont want
-    if response.players.max != 50 || response.favicon.is_none() || response.version.protocol != 760 {
-        return Ok(());
+    Ok(up.into_inner())
+}
+
+async fn scan_batch(args: &Args, config: ScanConfig) -> anyhow::Result<u64> {
+    let mut lists = vec![];
+    let mut ttls = HashMap::new();
+    for path in &args.input {
+        let entries = input::read_entries(path, args.format).with_context(|| format!("failed to read {}", path.display()))?;
+        let targets = match args.edition {
+            Edition::Java => input::targets(&entries),
+            Edition::Bedrock => input::bedrock_targets(&entries)
+        };
+
+        info!("{}: {} targets", path.display(), targets.len());
+        lists.push(targets);
+        ttls.extend(input::ttls(&entries));
     }
 
-    let mut file = tokio::fs::File::create(format!("data/{}.json", ip)).await?;
-    file.write_all(serde_json::to_string_pretty(&response)?.as_bytes()).await?;
+    let mut targets = input::order_targets(input::merge_targets(lists), args.order);
+    info!("{} targets in total after removing duplicates", targets.len());
 
-    // Extract the favicon
-    if let Some(favicon) = response.favicon {
-        let mut file = tokio::fs::File::create(format!("data/{}.png", ip)).await?;
-        file.write_all(&base64::decode(&favicon[22..])?).await?;
+    if let (Some(path), true) = (&args.checkpoint, args.resume) {
+        let finished = checkpoint::load_finished(path, args.retry_failures)?;
+        targets.retain(|target| !finished.contains(target));
+        info!("Resuming, {} targets left", targets.len());
+    }
+
+    #[cfg(debug_assertions)]
+    targets.truncate(1);
+
+    if args.preflight {
+        // bedrock is UDP, there is no connection to try
+        preflight(&targets, args.preflight_sample, Duration::from_secs(3), &config.resolver, args.edition == Edition::Java).await?;
+    }
+
+    if args.sort_by.is_some() && args.output != OutputMode::Consolidated {
+        anyhow::bail!("--sort-by needs --output consolidated, the other outputs dont hold on to results");
+    }
+
+    if args.favicon_only && args.output != OutputMode::Files {
+        anyhow::bail!("--favicon-only needs --output files, favicons are saved as files next to the index");
+    }
+
+    if args.favicon_only && args.anonymize.is_some() {
+        anyhow::bail!("--favicon-only with --anonymize would write nothing, anonymized results have no favicon");
     }
-    
-    Ok(())
-}
 
-trait BytesMutExt: BufMut {
-    fn put_vi(&mut self, value: u32) where Self: Sized {
-        let mut tmp_buffer = [0u8; 5];
-        self.put(unsigned_varint::encode::u32(value, &mut tmp_buffer));
+    let output = OutputConfig {
+        write_concurrency: args.write_concurrency,
+        pretty: args.pretty || !args.compact, // per-file output defaults to pretty
+        favicon_only: args.favicon_only,
+        favicon_format: args.favicon_format,
+        max_files: args.max_output_files,
+        max_bytes: args.max_output_bytes,
+        ..Default::default()
+    };
+    tokio::fs::create_dir(&output.dir).await.unwrap_or_default(); // We dont care about the error
+
+    if args.edition == Edition::Bedrock {
+        // pongs are not scan results, only the per-file output knows them
+        if args.output != OutputMode::Files {
+            anyhow::bail!("--edition bedrock only supports --output files");
+        }
+        return scan_bedrock(targets, &config, &output, args.anonymize.as_deref()).await;
     }
 
-    fn put_str(&mut self, value: &str) where Self: Sized {
-        self.put_vi(value.len() as u32);
-        self.put(value.as_bytes());
+    if let Some(addr) = args.metrics_addr {
+        let metrics = config.metrics.clone();
+        tokio::spawn(async move {
+            if let Err(err) = metrics::serve(addr, metrics).await {
+                warn!("Metrics endpoint stopped: {}", err);
+            }
+        });
     }
+
+    let checkpoint = match &args.checkpoint {
+        Some(path) => Some(Checkpoint::open(path, args.resume).await?),
+        None => None
+    };
+
+    let output = Arc::new(output);
+    let metrics = config.metrics.clone();
+    let matches = match args.output {
+        OutputMode::Files => scan_into(targets, config, FileSink::new(output.clone()), output.write_concurrency, checkpoint, ttls, args.anonymize.as_deref()).await,
+        OutputMode::Consolidated => {
+            let sink = ConsolidatedSink::new(output.clone(), args.sort_by, args.group);
+            scan_into(targets, config, sink, output.write_concurrency, checkpoint, ttls, args.anonymize.as_deref()).await
+        },
+        OutputMode::Sqlite => {
+            let sink = SqliteSink::open(&args.db_path).with_context(|| format!("failed to open {}", args.db_path.display()))?;
+            scan_into(targets, config, sink, output.write_concurrency, checkpoint, ttls, args.anonymize.as_deref()).await
+        }
+    }?;
+
+    if args.timeout_histogram {
+        eprint!("Status latencies:\n{}", metrics.latency_histogram());
+    }
+
+    Ok(matches)
 }
 
-impl BytesMutExt for BytesMut { }
+/// With a `salt` the results are anonymized before they are written.
+async fn scan_into<S: OutputSink>(targets: Vec<Target>, config: ScanConfig, sink: S, write_concurrency: usize, checkpoint: Option<Checkpoint>, ttls: HashMap<Target, i16>, salt: Option<&str>) -> anyhow::Result<u64> {
+    let writer = ResultWriter::spawn(sink, write_concurrency);
+    let metrics = config.metrics.clone();
+
+    scan_outcomes(targets, config)
+        .for_each(|(target, result)| {
+            let (writer, checkpoint, ttls) = (&writer, &checkpoint, &ttls);
+            async move {
+                if let Some(checkpoint) = checkpoint {
+                    if let Err(err) = checkpoint.record(&target, Outcome::of(&result)).await {
+                        warn!("Failed to update the checkpoint: {}", err);
+                    }
+                }
+
+                // errors are already logged by the scanner
+                if let Some(mut result) = result.ok().filter(|result| result.matched) {
+                    if let Some(&ttl) = ttls.get(&target) {
+                        result.set_ttl(ttl);
+                    }
+                    if let Some(salt) = salt {
+                        anonymize::anonymize(&mut result, salt);
+                    }
+                    writer.send(result).await;
+                }
+            }
+        })
+        .await;
+
+    writer.finish().await?;
+
+    Ok(metrics.matches.load(Ordering::Relaxed))
+}
+
+/// Every server that answered counts as a match, there is no filter for bedrock.
+/// With a `salt` the results are anonymized before they are written.
+async fn scan_bedrock(targets: Vec<Target>, config: &ScanConfig, output: &OutputConfig, salt: Option<&str>) -> anyhow::Result<u64> {
+    let answered = futures::stream::iter(targets)
+        .map(|target| async move {
+            let result = async {
+                let mut motd = bedrock_scan(&target, config).await?;
+                let ip = match salt {
+                    Some(salt) => {
+                        anonymize::anonymize_bedrock(&mut motd);
+                        anonymize::hash_ip(&target.ip, salt)
+                    },
+                    None => target.ip.clone()
+                };
+                tokio::fs::write(output.dir.join(format!("{}.json", output::file_stem(&ip, target.port))), output.to_json(&motd)?).await?;
+                anyhow::Ok(())
+            };
+            (result.await, target)
+        })
+        .buffer_unordered(config.concurrency.max(1))
+        .filter(|(result, target)| {
+            if let Err(err) = result {
+                warn!("Bedrock scan of {}:{} failed: {}", target.ip, target.port, err);
+            }
+            futures::future::ready(result.is_ok())
+        })
+        .count()
+        .await;
+
+    Ok(answered as u64)
+}
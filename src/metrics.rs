@@ -0,0 +1,117 @@
+use std::{fmt::Write, net::SocketAddr, sync::{atomic::{AtomicI64, AtomicU64, Ordering}, Arc}};
+
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+use tracing::{debug, info};
+
+use crate::scan::ScanError;
+
+/// Upper bounds (exclusive) of the latency histogram buckets in ms, slower
+/// responses land in one more bucket after these.
+pub const LATENCY_BUCKETS_MS: [u64; 5] = [50, 100, 250, 500, 1000];
+
+/// Counters updated by the scanner as it goes, cheap enough to always keep.
+#[derive(Debug, Default)]
+pub struct ScanMetrics {
+    pub targets_completed: AtomicU64,
+    pub matches: AtomicU64,
+    pub in_flight: AtomicI64,
+    pub bytes_read: AtomicU64,
+    errors: [AtomicU64; ScanError::KINDS.len()],
+    latencies: [AtomicU64; LATENCY_BUCKETS_MS.len() + 1]
+}
+
+impl ScanMetrics {
+    pub fn record_error(&self, err: &ScanError) {
+        let index = ScanError::KINDS.iter().position(|kind| *kind == err.kind()).unwrap_or(0);
+        self.errors[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn errors(&self, kind: &str) -> u64 {
+        ScanError::KINDS.iter().position(|k| *k == kind).map_or(0, |index| self.errors[index].load(Ordering::Relaxed))
+    }
+
+    pub fn record_latency(&self, latency_ms: u64) {
+        let index = LATENCY_BUCKETS_MS.iter().position(|&bound| latency_ms < bound).unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.latencies[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renders the status latencies and timeouts as a text histogram, with
+    /// the share of responders each bucket and everything below it covers,
+    /// to pick a timeout from.
+    pub fn latency_histogram(&self) -> String {
+        let mut labels: Vec<String> = LATENCY_BUCKETS_MS.iter().map(|bound| format!("<{}ms", bound)).collect();
+        labels.push(format!(">={}ms", LATENCY_BUCKETS_MS[LATENCY_BUCKETS_MS.len() - 1]));
+
+        let counts: Vec<u64> = self.latencies.iter().map(|count| count.load(Ordering::Relaxed)).collect();
+        let timeouts = self.errors("timeout") + self.errors("scan_timeout");
+        let responders: u64 = counts.iter().sum();
+        let widest = counts.iter().copied().chain([timeouts]).max().unwrap_or(0).max(1);
+
+        let mut out = String::new();
+        let mut bar = |label: &str, count: u64, covered: Option<u64>| {
+            let bar = "#".repeat((count * 40).div_ceil(widest) as usize);
+            let covered = covered.map_or(String::new(), |covered| format!("{:>5.1}%", covered as f64 * 100.0 / responders.max(1) as f64));
+            writeln!(out, "{}", format!("{:>8} {:>8} {:<40} {}", label, count, bar, covered).trim_end()).unwrap();
+        };
+
+        let mut covered = 0;
+        for (label, &count) in labels.iter().zip(&counts) {
+            covered += count;
+            bar(label, count, Some(covered));
+        }
+        bar("timeout", timeouts, None);
+
+        out
+    }
+
+    /// Renders the counters in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let mut counter = |name: &str, help: &str, value: u64| {
+            writeln!(out, "# HELP {} {}\n# TYPE {} counter\n{} {}", name, help, name, name, value).unwrap();
+        };
+        counter("quickie_targets_completed_total", "Targets that finished scanning, successful or not.", self.targets_completed.load(Ordering::Relaxed));
+        counter("quickie_matches_total", "Targets that passed the filter.", self.matches.load(Ordering::Relaxed));
+        counter("quickie_bytes_read_total", "Bytes read from status connections.", self.bytes_read.load(Ordering::Relaxed));
+
+        writeln!(out, "# HELP quickie_in_flight Connections currently being scanned.\n# TYPE quickie_in_flight gauge").unwrap();
+        writeln!(out, "quickie_in_flight {}", self.in_flight.load(Ordering::Relaxed)).unwrap();
+
+        writeln!(out, "# HELP quickie_errors_total Failed targets by kind of error.\n# TYPE quickie_errors_total counter").unwrap();
+        for (kind, count) in ScanError::KINDS.iter().zip(&self.errors) {
+            writeln!(out, "quickie_errors_total{{kind=\"{}\"}} {}", kind, count.load(Ordering::Relaxed)).unwrap();
+        }
+
+        out
+    }
+}
+
+/// Serves `GET /metrics` on `addr` until the process exits.
+pub async fn serve(addr: SocketAddr, metrics: Arc<ScanMetrics>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Serving metrics on http://{}/metrics", listener.local_addr()?);
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+
+        tokio::spawn(async move {
+            // we only care about the request line, which fits easily
+            let mut request = [0u8; 1024];
+            let len = stream.read(&mut request).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..len]);
+
+            let response = if request.starts_with("GET /metrics ") {
+                let body = metrics.render();
+                format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body)
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+            };
+
+            if let Err(err) = stream.write_all(response.as_bytes()).await {
+                debug!("Failed to answer metrics request: {}", err);
+            }
+        });
+    }
+}
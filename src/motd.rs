@@ -0,0 +1,121 @@
+use serde::{Serialize, Deserialize, Deserializer};
+
+/// 1.8.x, the last release where `players.sample` is the actual online list.
+pub const PROTOCOL_1_8: i32 = 47;
+
+/// A chat component, servers send the description as a plain string, an
+/// object with `text` and `extra` or occasionally an array of components.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MOTDDescription {
+    Text(String),
+    List(Vec<MOTDDescription>),
+    Component {
+        #[serde(default)]
+        text: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        extra: Vec<MOTDDescription>,
+        /// color, bold, translate and friends, kept so the output stays faithful
+        #[serde(flatten)]
+        style: serde_json::Map<String, serde_json::Value>
+    }
+}
+
+impl MOTDDescription {
+    /// The text of this component and all its children, without formatting codes.
+    pub fn plain_text(&self) -> String {
+        let mut text = String::new();
+        self.collect_text(&mut text);
+        strip_formatting(&text)
+    }
+
+    fn collect_text(&self, out: &mut String) {
+        match self {
+            MOTDDescription::Text(text) => out.push_str(text),
+            MOTDDescription::List(components) => components.iter().for_each(|component| component.collect_text(out)),
+            MOTDDescription::Component { text, extra, .. } => {
+                out.push_str(text);
+                extra.iter().for_each(|component| component.collect_text(out));
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MOTDPlayers {
+    pub max: u32,
+    pub online: u32,
+    pub sample: Option<Vec<MOTDPlayer>>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MOTDPlayer {
+    pub name: String,
+    pub id: String
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MOTDVersion {
+    /// Kept as sent, some servers put a chat component in here which we keep as raw JSON.
+    #[serde(deserialize_with = "string_or_raw")]
+    pub name: String,
+    pub protocol: i32
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MOTD {
+    pub description: MOTDDescription,
+    pub players: MOTDPlayers,
+    pub version: MOTDVersion,
+    pub favicon: Option<String>,
+    /// 1.19.1+, whether players without signed chat are kicked.
+    #[serde(rename = "enforcesSecureChat", default, skip_serializing_if = "Option::is_none")]
+    pub enforces_secure_chat: Option<bool>,
+    /// 1.19 to 1.19.2 only, chat preview was removed again in 1.19.3.
+    #[serde(rename = "previewsChat", default, skip_serializing_if = "Option::is_none")]
+    pub previews_chat: Option<bool>
+}
+
+impl MOTD {
+    /// Whether `players.sample` can be trusted to list who is online.
+    ///
+    /// 1.8 servers fill it with the real players, newer ones (and most
+    /// plugins) randomize it or put arbitrary text in there.
+    pub fn sample_reliable(&self) -> bool {
+        self.version.protocol == PROTOCOL_1_8
+    }
+
+    /// The names of the online players, when the server era reports them reliably.
+    pub fn online_player_names(&self) -> Option<Vec<String>> {
+        if !self.sample_reliable() {
+            return None;
+        }
+
+        let sample = self.players.sample.as_ref()?;
+        Some(sample.iter().map(|player| player.name.clone()).collect())
+    }
+}
+
+/// Removes `§` formatting codes (colors, bold, ...) along with their code character.
+pub fn strip_formatting(text: &str) -> String {
+    let mut clean = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next(); // the code itself
+        } else {
+            clean.push(c);
+        }
+    }
+
+    clean
+}
+
+fn string_or_raw<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(name) => name,
+        other => other.to_string()
+    })
+}
@@ -0,0 +1,241 @@
+use std::{future::Future, io::Write, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, AtomicU32, Ordering}, Arc, Mutex}};
+
+use futures::StreamExt;
+use serde::Serialize;
+use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::{error, warn};
+
+use crate::scan::ScanResult;
+
+mod consolidated;
+mod files;
+mod sqlite;
+
+pub use consolidated::{ConsolidatedSink, SortBy};
+pub use files::{FaviconFormat, FileSink, IndexEntry};
+pub use sqlite::SqliteSink;
+
+#[derive(Clone, Debug)]
+pub struct OutputConfig {
+    /// Directory the results are written to.
+    pub dir: PathBuf,
+    /// How many results are written at the same time, independent of the scan concurrency.
+    pub write_concurrency: usize,
+    /// Pretty-print the JSON, compact output is smaller and faster to parse downstream.
+    pub pretty: bool,
+    /// Only save favicons, no result JSON (the index is still written).
+    pub favicon_only: bool,
+    /// What the favicons are converted to before they are saved.
+    pub favicon_format: FaviconFormat,
+    /// Stop writing new result files once this many were written.
+    pub max_files: Option<u64>,
+    /// Stop writing new result files once they add up to this many bytes.
+    pub max_bytes: Option<u64>
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self { dir: PathBuf::from("data"), write_concurrency: 16, pretty: true, favicon_only: false, favicon_format: FaviconFormat::Png, max_files: None, max_bytes: None }
+    }
+}
+
+impl OutputConfig {
+    pub fn to_json<T: Serialize>(&self, value: &T) -> serde_json::Result<String> {
+        if self.pretty {
+            serde_json::to_string_pretty(value)
+        } else {
+            serde_json::to_string(value)
+        }
+    }
+}
+
+/// What the files of a result are called (plus an extension). Both ip and
+/// port, so a host with several servers gets a file for each instead of
+/// the last one overwriting the others.
+pub fn file_stem(ip: &str, port: u16) -> String {
+    format!("{}_{}", ip, port)
+}
+
+/// Somewhere matching results end up.
+/// A sink that writes results in batches fails with this, so all results
+/// of the batch get printed instead of just the one that filled it.
+pub struct BatchFailed {
+    pub results: Vec<ScanResult>,
+    pub source: anyhow::Error
+}
+
+impl std::fmt::Display for BatchFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "writing a batch of {} results failed", self.results.len())
+    }
+}
+
+// results have no Debug, and hundreds of them would not help anyway
+impl std::fmt::Debug for BatchFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BatchFailed").field("results", &self.results.len()).field("source", &self.source).finish()
+    }
+}
+
+impl std::error::Error for BatchFailed {
+    // the error itself rather than a box of it, so it can still be told apart
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+pub trait OutputSink: Send + Sync + 'static {
+    /// Stores one result, called from several writer tasks at once. It is
+    /// only borrowed so a failed write can still be printed instead. Only
+    /// I/O and database errors count towards giving up on the output.
+    fn write(&self, result: &ScanResult) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    /// Called once after the last result was written.
+    fn finish(&self) -> impl Future<Output = anyhow::Result<()>> + Send;
+}
+
+// consecutive failed writes after which we stop writing altogether
+const WRITE_FAILURE_LIMIT: u32 = 8;
+
+/// Writes results on its own pool of workers so slow storage doesn't stall the scan.
+///
+/// Results are handed over through a bounded channel, the scan only waits
+/// when the writers are that far behind.
+pub struct ResultWriter<S> {
+    sink: Arc<S>,
+    sender: mpsc::Sender<ScanResult>,
+    task: JoinHandle<()>,
+    fallback: Arc<Fallback>
+}
+
+/// Tracks failing writes, once the output looks broken for good (disk
+/// full, directory gone read-only) results go to stdout as JSON lines
+/// instead, so the scan is not wasted. Results whose write failed are
+/// printed as well.
+struct Fallback {
+    failures: AtomicU32,
+    active: AtomicBool,
+    out: Mutex<Box<dyn Write + Send>>
+}
+
+impl Fallback {
+    fn succeeded(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+    }
+
+    fn failed(&self) {
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= WRITE_FAILURE_LIMIT && !self.active.swap(true, Ordering::Relaxed) {
+            error!("{} writes in a row failed, the output seems broken. Not writing anymore, printing matches to stdout instead", failures);
+        }
+    }
+
+    /// Prints what a failed write left unsaved, the whole batch if it was one.
+    fn print_failed(&self, result: &ScanResult, err: &anyhow::Error) {
+        match err.downcast_ref::<BatchFailed>() {
+            Some(batch) => batch.results.iter().for_each(|result| self.print(result)),
+            None => self.print(result)
+        }
+    }
+
+    fn print(&self, result: &ScanResult) {
+        let printed = serde_json::to_string(result).map_err(anyhow::Error::from)
+            .and_then(|json| Ok(writeln!(self.out.lock().unwrap(), "{}", json)?));
+        if let Err(err) = printed {
+            warn!("Failed to print {}: {}", result.ip, err);
+        }
+    }
+}
+
+impl<S: OutputSink> ResultWriter<S> {
+    pub fn spawn(sink: S, write_concurrency: usize) -> Self {
+        Self::spawn_with_fallback(sink, write_concurrency, std::io::stdout())
+    }
+
+    /// Like [`ResultWriter::spawn`], with the results failed writes leave
+    /// behind going to `fallback` instead of stdout.
+    pub fn spawn_with_fallback(sink: S, write_concurrency: usize, fallback: impl Write + Send + 'static) -> Self {
+        let write_concurrency = write_concurrency.max(1);
+        let (sender, receiver) = mpsc::channel::<ScanResult>(write_concurrency * 16);
+        let sink = Arc::new(sink);
+        let fallback = Arc::new(Fallback { failures: AtomicU32::new(0), active: AtomicBool::new(false), out: Mutex::new(Box::new(fallback)) });
+
+        let results = futures::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|result| (result, receiver))
+        });
+
+        let task = tokio::spawn(results.for_each_concurrent(write_concurrency, {
+            let (sink, fallback) = (sink.clone(), fallback.clone());
+            move |result| {
+                let (sink, fallback) = (sink.clone(), fallback.clone());
+                async move {
+                    if fallback.active.load(Ordering::Relaxed) {
+                        return fallback.print(&result);
+                    }
+
+                    match sink.write(&result).await {
+                        Ok(()) => fallback.succeeded(),
+                        Err(err) => {
+                            warn!("Failed to save {}: {:#}", result.ip, err);
+                            // bad server data says nothing about the output
+                            if is_storage_failure(&err) {
+                                fallback.failed();
+                            }
+                            fallback.print_failed(&result, &err);
+                        }
+                    }
+                }
+            }
+        }));
+
+        Self { sink, sender, task, fallback }
+    }
+
+    /// The sink the results end up in, for whatever it offers beyond [`OutputSink`].
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// Queues a result, waiting only if the queue is full.
+    pub async fn send(&self, result: ScanResult) {
+        // the receiver only goes away once we are finished
+        self.sender.send(result).await.ok();
+    }
+
+    /// Waits for every queued result to be written and lets the sink wrap up.
+    pub async fn finish(self) -> anyhow::Result<()> {
+        drop(self.sender);
+        // a panicking write would otherwise leave the output silently incomplete
+        self.task.await?;
+
+        let finished = self.sink.finish().await;
+        // what was still batched up is not lost either
+        if let Some(batch) = finished.as_ref().err().and_then(|err| err.downcast_ref::<BatchFailed>()) {
+            batch.results.iter().for_each(|result| self.fallback.print(result));
+        }
+
+        // the matches are on stdout already, dont fail the whole run over the index
+        match finished {
+            Err(err) if self.fallback.active.load(Ordering::Relaxed) => {
+                warn!("Failed to finish the output: {:#}", err);
+                Ok(())
+            },
+            finished => finished
+        }
+    }
+}
+
+/// Whether a failed write was the storage failing (disk full, database
+/// locked, ...), rather than something about the result itself.
+fn is_storage_failure(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| cause.is::<std::io::Error>() || cause.is::<rusqlite::Error>())
+}
+
+/// Writes next to `path` and renames, so readers never see half a file.
+async fn write_atomic(path: &Path, contents: String) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    tokio::fs::write(&tmp, contents).await?;
+    tokio::fs::rename(&tmp, path).await
+}
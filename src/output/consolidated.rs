@@ -0,0 +1,99 @@
+use std::{collections::BTreeMap, sync::{Arc, Mutex}};
+
+use crate::scan::ScanResult;
+
+use super::{write_atomic, OutputConfig, OutputSink};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(clap::ValueEnum)]
+pub enum SortBy {
+    /// Lowest protocol first.
+    Protocol,
+    /// Busiest servers first.
+    PlayersOnline,
+    /// Fastest servers first.
+    Latency
+}
+
+impl SortBy {
+    fn sort(self, results: &mut [ScanResult]) {
+        match self {
+            SortBy::Protocol => results.sort_by_key(|result| result.motd.version.protocol),
+            SortBy::PlayersOnline => results.sort_by_key(|result| std::cmp::Reverse(result.motd.players.online)),
+            SortBy::Latency => results.sort_by_key(|result| result.latency_ms)
+        }
+    }
+
+    /// The group a result falls into, counts and latencies are bucketed.
+    fn group(self, result: &ScanResult) -> String {
+        match self {
+            SortBy::Protocol => result.motd.version.protocol.to_string(),
+            SortBy::PlayersOnline => match result.motd.players.online {
+                0 => "0",
+                1..=9 => "1-9",
+                10..=99 => "10-99",
+                100..=999 => "100-999",
+                _ => "1000+"
+            }.to_string(),
+            SortBy::Latency => match result.latency_ms {
+                0..=49 => "<50ms",
+                50..=99 => "<100ms",
+                100..=249 => "<250ms",
+                250..=499 => "<500ms",
+                _ => ">=500ms"
+            }.to_string()
+        }
+    }
+}
+
+/// Every result in a single `results.json`, written once the scan is done.
+///
+/// Sorting and grouping need all results at once, so this sink holds every
+/// match in memory until the end. That is fine for the usual few thousand
+/// matches, but for huge scans with loose filters the per-file output, which
+/// keeps nothing around, is the better choice.
+pub struct ConsolidatedSink {
+    config: Arc<OutputConfig>,
+    sort_by: Option<SortBy>,
+    /// Write an object of `group -> results` instead of a flat array.
+    group: bool,
+    results: Mutex<Vec<ScanResult>>
+}
+
+impl ConsolidatedSink {
+    pub fn new(config: Arc<OutputConfig>, sort_by: Option<SortBy>, group: bool) -> Self {
+        Self { config, sort_by, group, results: Mutex::new(vec![]) }
+    }
+}
+
+impl OutputSink for ConsolidatedSink {
+    async fn write(&self, result: &ScanResult) -> anyhow::Result<()> {
+        self.results.lock().unwrap().push(result.clone());
+        Ok(())
+    }
+
+    async fn finish(&self) -> anyhow::Result<()> {
+        let mut results = std::mem::take(&mut *self.results.lock().unwrap());
+
+        // stable order first, so equal keys dont come out in completion order
+        results.sort_by(|a, b| (&a.ip, a.port).cmp(&(&b.ip, b.port)));
+        if let Some(sort_by) = self.sort_by {
+            sort_by.sort(&mut results);
+        }
+
+        let json = match self.sort_by {
+            Some(sort_by) if self.group => {
+                let mut groups: BTreeMap<String, Vec<ScanResult>> = BTreeMap::new();
+                for result in results {
+                    groups.entry(sort_by.group(&result)).or_default().push(result);
+                }
+                self.config.to_json(&groups)?
+            },
+            _ => self.config.to_json(&results)?
+        };
+
+        write_atomic(&self.config.dir.join("results.json"), json).await?;
+
+        Ok(())
+    }
+}
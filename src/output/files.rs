@@ -0,0 +1,245 @@
+use std::{collections::{BTreeMap, HashSet}, io::{self, Cursor}, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{codecs::{jpeg::JpegEncoder, webp::WebPEncoder}, ImageFormat};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, warn};
+
+use crate::scan::{ScanResult, Target};
+
+use super::{file_stem, write_atomic, OutputConfig, OutputSink};
+
+// good enough for a 64x64 icon, below this the artifacts eat the pixel art
+const JPEG_QUALITY: u8 = 85;
+
+/// What favicons are saved as. Servers send a 64x64 PNG, a couple of KB each.
+///
+/// - PNG keeps them byte for byte as the server sent them.
+/// - WebP is lossless (the encoder we use can't do lossy), it keeps the
+///   transparency and usually ends up smaller.
+/// - JPEG drops the transparency (transparent pixels turn into whatever
+///   color is behind them, mostly black) and smears sharp pixel edges, and
+///   for flat icons it is not even always smaller. Only worth it when every
+///   byte counts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(clap::ValueEnum)]
+pub enum FaviconFormat {
+    #[default]
+    Png,
+    Webp,
+    Jpeg
+}
+
+impl FaviconFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            FaviconFormat::Png => "png",
+            FaviconFormat::Webp => "webp",
+            FaviconFormat::Jpeg => "jpg"
+        }
+    }
+
+    /// Re-encodes a decoded favicon, PNGs are passed through untouched.
+    ///
+    /// The icons are tiny, encoding one takes well under a millisecond so
+    /// this does not need the blocking pool.
+    pub fn convert(self, png: Vec<u8>) -> image::ImageResult<Vec<u8>> {
+        if self == FaviconFormat::Png {
+            return Ok(png);
+        }
+
+        let image = image::load_from_memory_with_format(&png, ImageFormat::Png)?;
+        let mut converted = Cursor::new(vec![]);
+        match self {
+            FaviconFormat::Png => unreachable!(),
+            FaviconFormat::Webp => image.write_with_encoder(WebPEncoder::new_lossless(&mut converted))?,
+            FaviconFormat::Jpeg => image.to_rgb8().write_with_encoder(JpegEncoder::new_with_quality(&mut converted, JPEG_QUALITY))?
+        }
+        Ok(converted.into_inner())
+    }
+}
+
+/// The PNG inside a favicon data url, the server sends whatever it likes in there.
+fn decode_favicon(favicon: &str) -> anyhow::Result<Vec<u8>> {
+    let base64 = favicon.strip_prefix("data:image/png;base64,").ok_or_else(|| anyhow::anyhow!("favicon is not a base64 PNG data url"))?;
+    Ok(STANDARD.decode(base64)?)
+}
+
+/// One line of `index.json`, enough to find interesting servers without
+/// opening every result file.
+#[derive(Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub ip: String,
+    pub port: u16,
+    pub version: String,
+    pub protocol: i32,
+    pub players_online: u32,
+    pub players_max: u32,
+    /// Relative to the output directory.
+    pub favicon: Option<PathBuf>,
+    pub latency_ms: u64,
+    /// `None` for servers older than 1.19.1.
+    pub enforces_secure_chat: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub campaign: Option<String>,
+    /// Did not answer the last recheck, the rest is from when it last did.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub offline: bool
+}
+
+/// A `{ip}_{port}.json` (and favicon) per server, plus an `index.json` once done. With
+/// `favicon_only` just the favicons.
+///
+/// The index is merged into the one already in the directory, servers of
+/// earlier runs stay listed until a newer result replaces them.
+///
+/// Once `max_files` or `max_bytes` of the config is reached further results
+/// are dropped, the scan itself keeps going (and counting matches).
+pub struct FileSink {
+    config: Arc<OutputConfig>,
+    index: Mutex<Vec<IndexEntry>>,
+    offline: Mutex<HashSet<(String, u16)>>,
+    written: Mutex<Written>
+}
+
+#[derive(Default)]
+struct Written {
+    files: u64,
+    bytes: u64,
+    limit_hit: bool
+}
+
+impl FileSink {
+    pub fn new(config: Arc<OutputConfig>) -> Self {
+        Self { config, index: Mutex::new(vec![]), offline: Mutex::default(), written: Mutex::default() }
+    }
+
+    /// Flags the index entry of `target` as offline when the index is written.
+    pub fn mark_offline(&self, target: &Target) {
+        self.offline.lock().unwrap().insert((target.ip.clone(), target.port));
+    }
+
+    /// Reserves room for a result, `false` once it would go over a limit.
+    fn reserve(&self, files: u64, bytes: u64) -> bool {
+        let mut written = self.written.lock().unwrap();
+
+        let over = self.config.max_files.is_some_and(|max| written.files + files > max)
+            || self.config.max_bytes.is_some_and(|max| written.bytes + bytes > max);
+        if over {
+            if !written.limit_hit {
+                warn!("Output limit reached after {} files ({} bytes), not writing any more results", written.files, written.bytes);
+                written.limit_hit = true;
+            }
+            return false;
+        }
+
+        written.files += files;
+        written.bytes += bytes;
+        true
+    }
+}
+
+impl OutputSink for FileSink {
+    async fn write(&self, result: &ScanResult) -> anyhow::Result<()> {
+        let config = &self.config;
+        let mut result = result.clone(); // gets the favicon path
+
+        // a broken favicon is the server's problem, the rest of the result is still worth keeping
+        let favicon = result.motd.favicon.as_deref().and_then(|favicon| decode_favicon(favicon)
+            .inspect_err(|err| debug!("Could not decode the favicon of {}: {}", result.ip, err))
+            .ok());
+        let favicon = favicon.map(|png| match config.favicon_format.convert(png.clone()) {
+            Ok(converted) => (converted, config.favicon_format),
+            // not a valid image, keep what the server sent
+            Err(err) => {
+                debug!("Could not convert the favicon of {}: {}", result.ip, err);
+                (png, FaviconFormat::Png)
+            }
+        });
+        let favicon = favicon.map(|(favicon, format)| {
+            result.favicon_path = Some(PathBuf::from(format!("{}.{}", file_stem(&result.ip, result.port), format.extension())));
+            favicon
+        });
+        if config.favicon_only && favicon.is_none() {
+            return Ok(());
+        }
+
+        let json = match config.favicon_only {
+            true => None,
+            false => Some(config.to_json(&result)?)
+        };
+
+        let files = json.is_some() as u64 + favicon.is_some() as u64;
+        let bytes = (json.as_ref().map_or(0, String::len) + favicon.as_ref().map_or(0, Vec::len)) as u64;
+        if !self.reserve(files, bytes) {
+            return Ok(());
+        }
+
+        if let Some(json) = json {
+            let mut file = tokio::fs::File::create(config.dir.join(format!("{}.json", file_stem(&result.ip, result.port)))).await?;
+            file.write_all(json.as_bytes()).await?;
+        }
+
+        // Extract the favicon
+        if let (Some(favicon), Some(path)) = (favicon, &result.favicon_path) {
+            let mut file = tokio::fs::File::create(config.dir.join(path)).await?;
+            file.write_all(&favicon).await?;
+        }
+
+        self.index.lock().unwrap().push(IndexEntry {
+            ip: result.ip.clone(),
+            port: result.port,
+            version: result.version_name_clean.clone(),
+            protocol: result.motd.version.protocol,
+            players_online: result.motd.players.online,
+            players_max: result.motd.players.max,
+            favicon: result.favicon_path.clone(),
+            latency_ms: result.latency_ms,
+            enforces_secure_chat: result.motd.enforces_secure_chat,
+            campaign: result.campaign.clone(),
+            offline: false
+        });
+
+        Ok(())
+    }
+
+    async fn finish(&self) -> anyhow::Result<()> {
+        let path = self.config.dir.join("index.json");
+
+        // keyed by ip and port, which also keeps the index sorted
+        let mut index: BTreeMap<(String, u16), IndexEntry> = read_index(&path).await?.into_iter()
+            .map(|entry| ((entry.ip.clone(), entry.port), entry))
+            .collect();
+
+        let offline = std::mem::take(&mut *self.offline.lock().unwrap());
+        for (server, entry) in index.iter_mut() {
+            entry.offline |= offline.contains(server);
+        }
+
+        // results of this run replace whatever an earlier one saved
+        for entry in std::mem::take(&mut *self.index.lock().unwrap()) {
+            index.insert((entry.ip.clone(), entry.port), entry);
+        }
+
+        let index: Vec<IndexEntry> = index.into_values().collect();
+        write_atomic(&path, self.config.to_json(&index)?).await?;
+
+        Ok(())
+    }
+}
+
+/// The entries of an existing index, none if there is none yet. A broken
+/// one is started over rather than failing the whole output.
+async fn read_index(path: &Path) -> io::Result<Vec<IndexEntry>> {
+    let index = match tokio::fs::read(path).await {
+        Ok(index) => index,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err)
+    };
+
+    Ok(serde_json::from_slice(&index).unwrap_or_else(|err| {
+        warn!("{} is not a valid index, starting a new one: {}", path.display(), err);
+        vec![]
+    }))
+}
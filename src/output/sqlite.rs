@@ -0,0 +1,137 @@
+use std::{path::Path, sync::{Arc, Mutex}};
+
+use rusqlite::{params, Connection};
+
+use crate::scan::ScanResult;
+
+use super::{BatchFailed, OutputSink};
+
+// results are inserted this many at a time, one transaction each
+const BATCH_SIZE: usize = 256;
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+
+    CREATE TABLE IF NOT EXISTS servers (
+        id INTEGER PRIMARY KEY,
+        ip TEXT NOT NULL UNIQUE,
+        first_seen INTEGER NOT NULL,
+        last_seen INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS ports (
+        server_id INTEGER NOT NULL REFERENCES servers (id),
+        port INTEGER NOT NULL,
+        scanned_at INTEGER NOT NULL,
+        version TEXT NOT NULL,
+        protocol INTEGER NOT NULL,
+        players_online INTEGER NOT NULL,
+        players_max INTEGER NOT NULL,
+        description TEXT NOT NULL,
+        latency_ms INTEGER NOT NULL,
+        online_mode INTEGER,
+        favicon TEXT,
+        campaign TEXT,
+        -- the whole result as JSON, for everything without a column
+        result TEXT NOT NULL,
+        PRIMARY KEY (server_id, port)
+    );
+";
+
+/// Results in a SQLite database, a row per ip in `servers` and one per
+/// open port in `ports`.
+///
+/// Scanning into an existing database updates it, a port keeps only its
+/// latest result.
+pub struct SqliteSink {
+    connection: Arc<Mutex<Connection>>,
+    pending: Mutex<Vec<ScanResult>>
+}
+
+impl SqliteSink {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+
+        // databases from before labels dont have the column yet
+        let has_campaign: bool = connection.query_row("SELECT COUNT(*) > 0 FROM pragma_table_info('ports') WHERE name = 'campaign'", [], |row| row.get(0))?;
+        if !has_campaign {
+            connection.execute_batch("ALTER TABLE ports ADD COLUMN campaign TEXT")?;
+        }
+
+        Ok(Self { connection: Arc::new(Mutex::new(connection)), pending: Mutex::default() })
+    }
+
+    /// Inserts a batch on the blocking pool, sqlite calls would stall the writer tasks.
+    async fn insert(&self, results: Vec<ScanResult>) -> anyhow::Result<()> {
+        if results.is_empty() {
+            return Ok(());
+        }
+
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || match insert_batch(&mut connection.lock().unwrap(), &results) {
+            Ok(()) => Ok(()),
+            // the transaction is rolled back, none of the batch made it in
+            Err(source) => Err(BatchFailed { results, source }.into())
+        }).await?
+    }
+}
+
+impl OutputSink for SqliteSink {
+    async fn write(&self, result: &ScanResult) -> anyhow::Result<()> {
+        let batch = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.push(result.clone());
+            match pending.len() >= BATCH_SIZE {
+                true => std::mem::take(&mut *pending),
+                false => return Ok(())
+            }
+        };
+
+        self.insert(batch).await
+    }
+
+    async fn finish(&self) -> anyhow::Result<()> {
+        let rest = std::mem::take(&mut *self.pending.lock().unwrap());
+        self.insert(rest).await
+    }
+}
+
+fn insert_batch(connection: &mut Connection, results: &[ScanResult]) -> anyhow::Result<()> {
+    let transaction = connection.transaction()?;
+
+    {
+        let mut server = transaction.prepare_cached(
+            "INSERT INTO servers (ip, first_seen, last_seen) VALUES (?1, ?2, ?2)
+             ON CONFLICT (ip) DO UPDATE SET last_seen = excluded.last_seen
+             RETURNING id"
+        )?;
+        let mut port = transaction.prepare_cached(
+            "INSERT OR REPLACE INTO ports
+             (server_id, port, scanned_at, version, protocol, players_online, players_max, description, latency_ms, online_mode, favicon, campaign, result)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)"
+        )?;
+
+        for result in results {
+            let server_id: i64 = server.query_row(params![result.ip, result.timestamp], |row| row.get(0))?;
+            port.execute(params![
+                server_id,
+                result.port,
+                result.timestamp,
+                result.version_name_clean,
+                result.motd.version.protocol,
+                result.motd.players.online,
+                result.motd.players.max,
+                result.motd.description.plain_text(),
+                result.latency_ms,
+                result.online_mode,
+                result.motd.favicon,
+                result.campaign,
+                serde_json::to_string(result)?
+            ])?;
+        }
+    }
+
+    transaction.commit()?;
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::{collections::HashSet, time::Duration};
+
+use futures::StreamExt;
+use tokio::{net::TcpStream, time::timeout};
+use tracing::{info, warn};
+
+use crate::{resolve::Resolver, scan::Target};
+
+#[derive(Debug)]
+pub struct PreflightReport {
+    pub hosts: usize,
+    pub unresolved: Vec<String>,
+    pub sampled: usize,
+    pub reachable: usize
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PreflightError {
+    #[error("none of the {0} hosts could be resolved, is the resolver working?")]
+    Unresolvable(usize),
+    #[error("none of the {0} sampled targets accepted a connection, this is most likely a network or firewall problem on our side")]
+    Unreachable(usize)
+}
+
+/// Resolves every host and tries to connect to a handful of targets, so a
+/// broken resolver or blocked egress shows up before a long scan starts.
+///
+/// The connect check is TCP, for UDP targets (bedrock) pass `connect: false`
+/// and only the resolving is checked.
+pub async fn preflight(targets: &[Target], sample: usize, connect_timeout: Duration, resolver: &Resolver, connect: bool) -> Result<PreflightReport, PreflightError> {
+    let hosts: HashSet<&str> = targets.iter().map(|target| target.ip.as_str()).collect();
+
+    let unresolved: Vec<String> = futures::stream::iter(hosts.iter().copied())
+        .map(|host| async move { resolver.resolve(host, 0).await.map_or(Some(host.to_string()), |_| None) })
+        .buffer_unordered(64)
+        .filter_map(|host| async move { host })
+        .collect()
+        .await;
+
+    if !hosts.is_empty() && unresolved.len() == hosts.len() {
+        return Err(PreflightError::Unresolvable(hosts.len()));
+    }
+
+    for host in &unresolved {
+        warn!("Preflight: could not resolve {}", host);
+    }
+
+    // spread the sample over the whole list instead of hitting the first host only
+    let step = (targets.len() / sample.max(1)).max(1);
+    let sampled: Vec<&Target> = targets.iter().step_by(step).take(if connect { sample } else { 0 }).collect();
+
+    let reachable = futures::stream::iter(sampled.iter())
+        .map(|target| async move {
+            let connect = async {
+                let addrs = resolver.resolve(&target.ip, target.port).await?;
+                TcpStream::connect(&addrs[..]).await
+            };
+            matches!(timeout(connect_timeout, connect).await, Ok(Ok(_)))
+        })
+        .buffer_unordered(sample.max(1))
+        .filter(|reachable| futures::future::ready(*reachable))
+        .count()
+        .await;
+
+    if !sampled.is_empty() && reachable == 0 {
+        return Err(PreflightError::Unreachable(sampled.len()));
+    }
+
+    info!("Preflight: {}/{} hosts resolved, {}/{} sampled targets reachable", hosts.len() - unresolved.len(), hosts.len(), reachable, sampled.len());
+
+    Ok(PreflightReport { hosts: hosts.len(), unresolved, sampled: sampled.len(), reachable })
+}
@@ -0,0 +1,261 @@
+use std::{future::Future, io, time::Duration};
+
+use bytes::{ Bytes, BytesMut, BufMut, Buf };
+use tokio::{io::{AsyncRead, AsyncReadExt}, time::timeout};
+use tracing::warn;
+
+use crate::scan::ScanError;
+
+/// Next state requested in the handshake.
+pub const STATE_STATUS: u32 = 1;
+pub const STATE_LOGIN: u32 = 2;
+
+/// The most bytes a VarInt takes, 32 bits in groups of 7.
+pub const MAX_VARINT_LEN: usize = 5;
+
+pub trait BytesMutExt: BufMut {
+    fn put_vi(&mut self, value: u32) where Self: Sized {
+        // typed as our own bound, so this stops compiling should the
+        // encoder ever want a different buffer than we read back
+        let mut tmp_buffer: [u8; MAX_VARINT_LEN] = unsigned_varint::encode::u32_buffer();
+        let encoded = unsigned_varint::encode::u32(value, &mut tmp_buffer);
+        self.put(encoded);
+    }
+
+    /// Signed VarInt, negative numbers take all 5 bytes (two's complement).
+    fn put_varint_i32(&mut self, value: i32) where Self: Sized {
+        self.put_vi(value as u32);
+    }
+
+    /// Panics for strings longer than a VarInt can count (4GB), rather than
+    /// sending a wrapped around length.
+    fn put_str(&mut self, value: &str) where Self: Sized {
+        let len = u32::try_from(value.len()).expect("string too long for a VarInt length");
+        self.put_vi(len);
+        self.put(value.as_bytes());
+    }
+}
+
+impl BytesMutExt for BytesMut { }
+
+pub trait BufExt: Buf {
+    fn get_vi(&mut self) -> Option<u32> where Self: Sized {
+        let mut value = 0u32;
+
+        for i in 0..MAX_VARINT_LEN {
+            if !self.has_remaining() {
+                return None;
+            }
+
+            let byte = self.get_u8();
+            value |= ((byte & 0x7F) as u32) << (7 * i);
+
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+
+        None // longer than MAX_VARINT_LEN, not a valid VarInt
+    }
+
+    /// Signed VarInt, protocol numbers are these and can be negative (-1 is
+    /// sent by proxies that speak every version).
+    fn get_varint_i32(&mut self) -> Option<i32> where Self: Sized {
+        self.get_vi().map(|value| value as i32)
+    }
+
+    fn get_str(&mut self) -> Option<String> where Self: Sized {
+        let len = self.get_vi()? as usize;
+        if self.remaining() < len {
+            return None;
+        }
+
+        String::from_utf8(self.copy_to_bytes(len).to_vec()).ok()
+    }
+}
+
+impl<B: Buf> BufExt for B { }
+
+/// An uncompressed packet, its id and whatever follows it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Packet {
+    pub id: i32,
+    pub data: Bytes
+}
+
+impl Packet {
+    pub fn new(id: i32, data: impl Into<Bytes>) -> Self {
+        Self { id, data: data.into() }
+    }
+
+    /// `[packet length][packet id][data]`, ready to send.
+    pub fn encode(&self) -> BytesMut {
+        let mut packet_id = BytesMut::new();
+        packet_id.put_varint_i32(self.id);
+
+        let mut packet = BytesMut::new();
+        packet.put_vi((packet_id.len() + self.data.len()) as u32); // packet length
+        packet.put(packet_id); // packet id
+        packet.put(&self.data[..]); // packet data
+        packet
+    }
+
+    /// Takes one length-prefixed packet off the front of `buf`.
+    pub fn decode(buf: &mut impl Buf) -> Result<Packet, ScanError> {
+        let len = buf.get_vi().ok_or(ScanError::MalformedResponse)? as usize;
+        if buf.remaining() < len {
+            return Err(ScanError::MalformedResponse);
+        }
+
+        Self::from_frame(buf.copy_to_bytes(len))
+    }
+
+    /// Splits a frame that had its length taken off already.
+    pub fn from_frame(mut frame: Bytes) -> Result<Packet, ScanError> {
+        let id = frame.get_varint_i32().ok_or(ScanError::MalformedResponse)?;
+        Ok(Packet { id, data: frame })
+    }
+}
+
+/// Longest server address the handshake may carry, in characters.
+pub const MAX_HANDSHAKE_HOST: usize = 255;
+
+pub fn build_handshake(protocol_version: i32, host: &str, port: u16, next_state: u32) -> BytesMut {
+    // servers reject longer ones, cut it off at a character boundary
+    let host = match host.char_indices().nth(MAX_HANDSHAKE_HOST) {
+        Some((end, _)) => {
+            warn!("Handshake address is longer than {} characters, truncating it", MAX_HANDSHAKE_HOST);
+            &host[..end]
+        },
+        None => host
+    };
+
+    let mut handshake_data = BytesMut::new();
+    handshake_data.put_varint_i32(protocol_version); // protocol version
+    handshake_data.put_str(host); // ip
+    handshake_data.put_u16(port); // port
+    handshake_data.put_vi(next_state); // state
+
+    Packet::new(0x00, handshake_data).encode() // packet id (0x00 = handshake)
+}
+
+/// The error inside the `io::Error` of [`FramedReader::read_frame`] when a
+/// frame is over the limit, so callers can tell it apart from other i/o errors.
+#[derive(Debug, thiserror::Error)]
+#[error("packet of {len} bytes exceeds the {max_len} byte limit")]
+pub struct FrameTooLarge {
+    pub len: usize,
+    pub max_len: usize
+}
+
+/// Reads length-prefixed packets off a stream one at a time.
+///
+/// Only as many bytes as the packets themselves are read, so callers can
+/// stop after any packet without draining whatever the server sends next.
+/// Every read is bounded by `read_timeout`, a silent server fails with
+/// `io::ErrorKind::TimedOut`.
+pub struct FramedReader<S> {
+    stream: S,
+    read_timeout: Duration,
+    bytes_read: u64,
+    // read by `peek` but not handed out yet
+    peeked: Vec<u8>
+}
+
+impl<S: AsyncRead + Unpin> FramedReader<S> {
+    pub fn new(stream: S, read_timeout: Duration) -> Self {
+        Self { stream, read_timeout, bytes_read: 0, peeked: vec![] }
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Total bytes taken off the stream so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Looks at the next `len` bytes without taking them off, the following
+    /// reads still get them. Fewer when the stream ends before.
+    pub async fn peek(&mut self, len: usize) -> io::Result<&[u8]> {
+        while self.peeked.len() < len {
+            let mut buf = vec![0u8; len - self.peeked.len()];
+            let read = self.with_timeout(|stream, buf| stream.read(buf), &mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            self.peeked.extend_from_slice(&buf[..read]);
+        }
+
+        Ok(&self.peeked[..len.min(self.peeked.len())])
+    }
+
+    /// Reads the next packet, see [`FramedReader::read_frame`].
+    pub async fn read_packet(&mut self, max_len: usize) -> Result<Packet, ScanError> {
+        Packet::from_frame(self.read_frame(max_len).await?)
+    }
+
+    /// Reads the next frame, everything after the packet length.
+    pub async fn read_frame(&mut self, max_len: usize) -> io::Result<Bytes> {
+        let len = self.read_vi().await? as usize;
+        if len > max_len {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, FrameTooLarge { len, max_len }));
+        }
+
+        let mut frame = vec![0u8; len];
+        let mut filled = 0;
+
+        // a timeout per read rather than for the whole frame, big favicons take a while
+        while filled < len {
+            let read = self.read_some(&mut frame[filled..]).await?;
+            if read == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            filled += read;
+        }
+
+        self.bytes_read += len as u64;
+        Ok(frame.into())
+    }
+
+    async fn read_vi(&mut self) -> io::Result<u32> {
+        let mut value = 0u32;
+
+        for i in 0..MAX_VARINT_LEN {
+            let mut byte = [0u8; 1];
+            if self.read_some(&mut byte).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.bytes_read += 1;
+
+            value |= ((byte[0] & 0x7F) as u32) << (7 * i);
+            if byte[0] & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(io::Error::new(io::ErrorKind::InvalidData, "VarInt is longer than 5 bytes"))
+    }
+
+    /// Hands out peeked bytes first, then reads.
+    async fn read_some(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.peeked.is_empty() {
+            return self.with_timeout(|stream, buf| stream.read(buf), buf).await;
+        }
+
+        let len = buf.len().min(self.peeked.len());
+        buf[..len].copy_from_slice(&self.peeked[..len]);
+        self.peeked.drain(..len);
+        Ok(len)
+    }
+
+    async fn with_timeout<'a, F, Fut>(&'a mut self, read: F, buf: &'a mut [u8]) -> io::Result<usize>
+    where
+        F: FnOnce(&'a mut S, &'a mut [u8]) -> Fut,
+        Fut: Future<Output = io::Result<usize>>
+    {
+        timeout(self.read_timeout, read(&mut self.stream, buf)).await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use bytes::{ Buf, BufMut, BytesMut };
+use serde::{Serialize, Deserialize};
+use tokio::time::timeout;
+
+use crate::{scan::{ScanConfig, ScanError, Target}, transport::connect_udp};
+
+// GameSpy4 query, what the server speaks over UDP with `enable-query=true`
+const MAGIC: [u8; 2] = [0xfe, 0xfd];
+
+const TYPE_HANDSHAKE: u8 = 0x09;
+const TYPE_STAT: u8 = 0x00;
+
+// the session id has to stay within the low nibbles of every byte
+const SESSION_ID: u32 = 0x0102_0304;
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The full stat of the query protocol, some servers only fill this and not
+/// the status (`plugins`, the real player list).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryResponse {
+    pub hostname: String,
+    pub version: String,
+    /// `Paper on Bukkit 1.20.1: Essentials 2.19.0; ...` when the server shares them.
+    pub plugins: String,
+    pub map: String,
+    pub players_online: u32,
+    pub players_max: u32,
+    pub players: Vec<String>
+}
+
+/// Asks for the full stat, the port of `target` is the server's `query.port`
+/// (the game port unless configured otherwise).
+pub async fn query(target: &Target, config: &ScanConfig) -> Result<QueryResponse, ScanError> {
+    let socket = connect_udp(target, config).await?;
+
+    let mut buffer = [0u8; 4096]; // the player list is cut off long before this
+
+    // the handshake hands out a challenge token we have to send back
+    socket.send(&request(TYPE_HANDSHAKE, &[])).await?;
+    let len = timeout(TIMEOUT, socket.recv(&mut buffer)).await.map_err(|_| ScanError::Timeout)??;
+    let token = parse_challenge(&buffer[..len])?;
+
+    let mut payload = BytesMut::new();
+    payload.put_i32(token);
+    payload.put_u32(0); // padding asks for the full stat instead of the basic one
+    socket.send(&request(TYPE_STAT, &payload)).await?;
+    let len = timeout(TIMEOUT, socket.recv(&mut buffer)).await.map_err(|_| ScanError::Timeout)??;
+
+    parse_full_stat(&buffer[..len])
+}
+
+fn request(kind: u8, payload: &[u8]) -> BytesMut {
+    let mut packet = BytesMut::new();
+    packet.put(&MAGIC[..]);
+    packet.put_u8(kind);
+    packet.put_u32(SESSION_ID);
+    packet.put(payload);
+    packet
+}
+
+/// Checks type and session id and returns what follows.
+fn response_body(mut packet: &[u8], kind: u8) -> Result<&[u8], ScanError> {
+    if packet.remaining() < 5 || packet.get_u8() != kind || packet.get_u32() != SESSION_ID {
+        return Err(ScanError::MalformedResponse);
+    }
+
+    Ok(packet)
+}
+
+fn parse_challenge(packet: &[u8]) -> Result<i32, ScanError> {
+    let body = response_body(packet, TYPE_HANDSHAKE)?;
+
+    // the token comes as a null terminated decimal string
+    let token = body.split(|&byte| byte == 0).next().unwrap_or_default();
+    std::str::from_utf8(token).ok()
+        .and_then(|token| token.parse().ok())
+        .ok_or(ScanError::MalformedResponse)
+}
+
+fn parse_full_stat(packet: &[u8]) -> Result<QueryResponse, ScanError> {
+    let body = response_body(packet, TYPE_STAT)?;
+
+    // "splitnum\0\x80\0" before the values and "\x01player_\0\0" before the players
+    let body = body.get(11..).ok_or(ScanError::MalformedResponse)?;
+    let mut strings = body.split(|&byte| byte == 0).map(String::from_utf8_lossy);
+
+    let mut response = QueryResponse::default();
+    loop {
+        let key = strings.next().ok_or(ScanError::MalformedResponse)?;
+        if key.is_empty() {
+            break; // an empty key ends the values
+        }
+
+        let value = strings.next().ok_or(ScanError::MalformedResponse)?.into_owned();
+        match &*key {
+            "hostname" => response.hostname = value,
+            "version" => response.version = value,
+            "plugins" => response.plugins = value,
+            "map" => response.map = value,
+            "numplayers" => response.players_online = value.parse().unwrap_or_default(),
+            "maxplayers" => response.players_max = value.parse().unwrap_or_default(),
+            _ => {}
+        }
+    }
+
+    // skip the "\x01player_" marker and its empty value
+    response.players = strings.skip(2)
+        .take_while(|name| !name.is_empty())
+        .map(|name| name.into_owned())
+        .collect();
+
+    Ok(response)
+}
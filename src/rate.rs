@@ -0,0 +1,136 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use tokio::time::{sleep, Instant};
+use tracing::debug;
+
+// outcomes the adaptive rate looks at, and how often it adjusts (every quarter window)
+const WINDOW: usize = 200;
+const ADJUST_EVERY: usize = WINDOW / 4;
+
+// multiplicative both ways, backing off harder than speeding up
+const SPEED_UP: f64 = 1.1;
+const BACK_OFF: f64 = 0.7;
+
+/// The adaptive rate never goes above this many times the starting rate.
+pub const MAX_RATE_FACTOR: f64 = 8.0;
+const MIN_RATE: f64 = 1.0;
+
+/// A token bucket limiting how many scans start per second.
+///
+/// With a target error rate it adapts: every so often the share of recent
+/// scans that failed in a way that hints at congestion (timeouts, resets)
+/// is compared against the target. Above it the rate backs off, well below
+/// it the rate creeps up again.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+    adaptive: Option<Adaptive>
+}
+
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    refilled: Instant
+}
+
+#[derive(Debug)]
+struct Adaptive {
+    target_error_rate: f64,
+    max_rate: f64,
+    window: Mutex<Window>
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    // true for failures
+    outcomes: VecDeque<bool>,
+    since_adjust: usize
+}
+
+impl RateLimiter {
+    /// `rate` scans per second, adapting towards `target_error_rate` (0.05 for 5%) when given.
+    pub fn new(rate: f64, target_error_rate: Option<f64>) -> Self {
+        let rate = rate.max(MIN_RATE);
+
+        Self {
+            bucket: Mutex::new(Bucket { rate, tokens: burst(rate), refilled: Instant::now() }),
+            adaptive: target_error_rate.map(|target_error_rate| Adaptive {
+                target_error_rate,
+                max_rate: rate * MAX_RATE_FACTOR,
+                window: Mutex::default()
+            })
+        }
+    }
+
+    /// Current scans per second.
+    pub fn rate(&self) -> f64 {
+        self.bucket.lock().unwrap().rate
+    }
+
+    /// Waits until another scan may start.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                bucket.refill();
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / bucket.rate)
+            };
+            sleep(wait).await;
+        }
+    }
+
+    /// Feeds the outcome of a scan to the adaptive rate, does nothing for a fixed one.
+    pub fn record(&self, failed: bool) {
+        let Some(adaptive) = &self.adaptive else { return };
+
+        let error_rate = {
+            let mut window = adaptive.window.lock().unwrap();
+            window.outcomes.push_back(failed);
+            if window.outcomes.len() > WINDOW {
+                window.outcomes.pop_front();
+            }
+
+            window.since_adjust += 1;
+            if window.since_adjust < ADJUST_EVERY {
+                return;
+            }
+            window.since_adjust = 0;
+
+            window.outcomes.iter().filter(|&&failed| failed).count() as f64 / window.outcomes.len() as f64
+        };
+
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill();
+        let rate = if error_rate > adaptive.target_error_rate {
+            bucket.rate * BACK_OFF
+        } else if error_rate < adaptive.target_error_rate / 2.0 {
+            bucket.rate * SPEED_UP
+        } else {
+            bucket.rate
+        }.clamp(MIN_RATE, adaptive.max_rate);
+
+        if rate != bucket.rate {
+            debug!("{:.1}% of recent scans failed, rate {:.0}/s -> {:.0}/s", error_rate * 100.0, bucket.rate, rate);
+            bucket.rate = rate;
+            bucket.tokens = bucket.tokens.min(burst(rate));
+        }
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        self.tokens = (self.tokens + (now - self.refilled).as_secs_f64() * self.rate).min(burst(self.rate));
+        self.refilled = now;
+    }
+}
+
+/// A tenth of a second worth of scans can start at once.
+fn burst(rate: f64) -> f64 {
+    (rate / 10.0).max(1.0)
+}
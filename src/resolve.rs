@@ -0,0 +1,118 @@
+use std::{collections::HashMap, io, net::{IpAddr, SocketAddr}, sync::Mutex};
+
+use serde::Deserialize;
+use tokio::net::lookup_host;
+
+// record types we ask DoH servers for
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+/// Turns target hosts into addresses, remembering every answer for the rest
+/// of the scan so hosts with many open ports are only looked up once.
+///
+/// Ip addresses are passed through without asking anyone.
+#[derive(Debug, Default)]
+pub struct Resolver {
+    backend: Backend,
+    cache: Mutex<HashMap<String, Vec<IpAddr>>>
+}
+
+#[derive(Debug, Default)]
+enum Backend {
+    /// Whatever the OS is configured with.
+    #[default]
+    System,
+    /// A DNS-over-HTTPS endpoint speaking the JSON API
+    /// (`https://cloudflare-dns.com/dns-query`, `https://dns.google/resolve`).
+    DoH { endpoint: String, client: reqwest::Client }
+}
+
+#[derive(Deserialize)]
+struct DoHResponse {
+    #[serde(rename = "Status")]
+    status: u32,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DoHAnswer>
+}
+
+#[derive(Deserialize)]
+struct DoHAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    data: String
+}
+
+impl Resolver {
+    /// Resolves through the OS.
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Resolves through a DNS-over-HTTPS endpoint instead of the OS.
+    pub fn doh(endpoint: impl Into<String>) -> Self {
+        Self {
+            backend: Backend::DoH { endpoint: endpoint.into(), client: reqwest::Client::new() },
+            cache: Mutex::default()
+        }
+    }
+
+    pub async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![SocketAddr::new(ip, port)]);
+        }
+
+        let cached = self.cache.lock().unwrap().get(host).cloned();
+        let ips = match cached {
+            Some(ips) => ips,
+            None => {
+                let ips = self.lookup(host).await?;
+                self.cache.lock().unwrap().insert(host.to_string(), ips.clone());
+                ips
+            }
+        };
+
+        if ips.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} has no addresses", host)));
+        }
+
+        Ok(ips.into_iter().map(|ip| SocketAddr::new(ip, port)).collect())
+    }
+
+    async fn lookup(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        match &self.backend {
+            Backend::System => Ok(lookup_host((host, 0)).await?.map(|addr| addr.ip()).collect()),
+            Backend::DoH { endpoint, client } => {
+                let mut ips = doh_query(client, endpoint, host, TYPE_A).await?;
+                if ips.is_empty() {
+                    ips = doh_query(client, endpoint, host, TYPE_AAAA).await?;
+                }
+                Ok(ips)
+            }
+        }
+    }
+}
+
+async fn doh_query(client: &reqwest::Client, endpoint: &str, host: &str, record_type: u16) -> io::Result<Vec<IpAddr>> {
+    let response: DoHResponse = client.get(endpoint)
+        .query(&[("name", host), ("type", &record_type.to_string())])
+        .header(reqwest::header::ACCEPT, "application/dns-json")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(io::Error::other)?
+        .json()
+        .await
+        .map_err(io::Error::other)?;
+
+    // 3 is NXDOMAIN, everything else non-zero is the server failing
+    match response.status {
+        0 | 3 => {},
+        status => return Err(io::Error::other(format!("DoH lookup of {} failed with status {}", host, status)))
+    }
+
+    // CNAME records come along in the answer, only keep the addresses
+    Ok(response.answer.into_iter()
+        .filter(|answer| answer.record_type == record_type)
+        .filter_map(|answer| answer.data.parse().ok())
+        .collect())
+}
@@ -0,0 +1,712 @@
+use std::{collections::hash_map::DefaultHasher, hash::{Hash, Hasher}, io, path::PathBuf, sync::{atomic::Ordering, Arc}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use bytes::{ Buf, Bytes };
+use futures::{Stream, StreamExt};
+use serde::{Serialize, Deserialize};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tokio::{io::AsyncWriteExt, net::TcpStream, time::{sleep, timeout}};
+use tracing::{debug, info};
+
+use crate::{cooldown::HostCooldown, filter::Filter, honeypot::HoneypotDetector, legacy, login::{self, LoginProbe}, metrics::ScanMetrics, motd::{strip_formatting, MOTD}, protocol::{build_handshake, FrameTooLarge, FramedReader, Packet, STATE_STATUS}, query::{self, QueryResponse}, rate::RateLimiter, resolve::Resolver, status::{parse_status_packet, Status}, transport::{self, Transport}};
+
+/// A single `ip:port` to probe.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Target {
+    pub ip: String,
+    pub port: u16
+}
+
+/// Port Java servers listen on unless told otherwise.
+pub const JAVA_DEFAULT_PORT: u16 = 25565;
+
+impl Target {
+    /// Parses `host`, `host:port` or `[v6]:port`, falling back to `default_port`.
+    pub fn parse(value: &str, default_port: u16) -> Result<Self, String> {
+        let (host, port) = match value.rsplit_once(':') {
+            // a bare ipv6 address has colons but no port
+            Some((host, port)) if !host.contains(':') || host.ends_with(']') => (host, Some(port)),
+            _ => (value, None)
+        };
+
+        let port = match port {
+            Some(port) => port.parse().map_err(|_| format!("invalid port `{}`", port))?,
+            None => default_port
+        };
+
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err("missing host".to_string());
+        }
+
+        Ok(Target { ip: host.to_string(), port })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ScanConfig {
+    /// How many targets are probed at the same time.
+    pub concurrency: usize,
+    /// Protocol version announced in the handshake.
+    pub protocol_version: i32,
+    /// With more than one, each target is scanned announcing each of these
+    /// in turn (at most [`MAX_PROTOCOL_CANDIDATES`]) and the most complete
+    /// response is kept. `protocol_version` is not used then.
+    pub protocol_candidates: Vec<i32>,
+    /// How long we wait for more bytes before considering the response done.
+    pub read_timeout: Duration,
+    /// Follow up the status ping with a login attempt to find out whether the
+    /// server authenticates players against Mojang.
+    pub online_mode_probe: bool,
+    /// Consecutive connection failures after which a host's remaining ports
+    /// are skipped for a while, 0 disables it.
+    pub host_failure_threshold: u32,
+    /// How long a failing host is skipped, doubled each time it happens again.
+    pub host_cooldown: Duration,
+    /// Most scans started per second, `None` for as fast as `concurrency` allows.
+    pub rate: Option<f64>,
+    /// With a `rate`, adjust it to keep the share of scans failing with
+    /// timeouts and resets around this (0.05 for 5%), see [`RateLimiter`].
+    pub adaptive_rate: Option<f64>,
+    /// Upper bound of the random delay before each connect, so tasks released
+    /// together dont all hit the network in the same instant. Zero disables it.
+    pub connect_jitter: Duration,
+    /// Seed for the jitter, each task derives its own rng from it.
+    pub jitter_seed: u64,
+    /// Flag a host as a likely honeypot once this many of its ports send a
+    /// byte-identical status. `None` disables the check.
+    pub honeypot_threshold: Option<usize>,
+    /// Retry status JSON that is not valid UTF-8 with the bad bytes replaced.
+    pub lossy_utf8: bool,
+    /// Decides which results count as matches. Follow-up probes (ping,
+    /// login) are only sent to matching servers.
+    pub filter: Filter,
+    /// Measure the round trip of a status ping after the status.
+    pub ping: bool,
+    /// Ask matching servers for their full stat over the UDP query protocol.
+    pub query: bool,
+    /// Try the pre-1.7 ping when a server does not answer the modern one.
+    pub legacy_fallback: bool,
+    /// When the server reports a different protocol than we announced,
+    /// scan it once more announcing its own.
+    pub auto_version: bool,
+    /// Updated as targets complete, share it to watch a running scan.
+    pub metrics: Arc<ScanMetrics>,
+    /// Server address sent in the handshake instead of the target ip,
+    /// virtual-host proxies route on it.
+    pub handshake_host: Option<String>,
+    /// Port sent in the handshake instead of the one we connect to.
+    pub handshake_port: Option<u16>,
+    /// Close scan connections with a reset (`SO_LINGER` 0) instead of a
+    /// FIN, so they dont sit in TIME_WAIT and eat local ports. This is
+    /// an unclean close that may cut off data still in flight, only use
+    /// it for throwaway scan connections on very large scans.
+    pub reset_on_close: bool,
+    /// Largest status response we read, bigger ones fail with
+    /// [`ScanError::PacketTooLarge`].
+    pub max_packet_size: usize,
+    /// Wall-clock budget for a whole [`perform_scan`], on top of the read
+    /// timeout which a server can keep resetting by dribbling bytes.
+    pub per_scan_deadline: Duration,
+    /// Called with every completed scan, matched or not.
+    pub on_result: Option<ResultHook>,
+    /// Copied into [`ScanResult::campaign`], to tell scans apart once their
+    /// results are merged.
+    pub label: Option<String>,
+    /// Resolves hostnames targets, shared by all scans so each host is only
+    /// looked up once.
+    pub resolver: Arc<Resolver>
+}
+
+/// A callback for [`ScanConfig::on_result`].
+///
+/// It runs inline on the scan loop, so keep it cheap (bump a counter, push
+/// into a channel) and spawn a task for anything that waits.
+#[derive(Clone)]
+pub struct ResultHook(Arc<dyn Fn(&ScanResult) + Send + Sync>);
+
+impl ResultHook {
+    pub fn new(hook: impl Fn(&ScanResult) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+
+    fn call(&self, result: &ScanResult) {
+        (self.0)(result)
+    }
+}
+
+impl std::fmt::Debug for ResultHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ResultHook")
+    }
+}
+
+impl ScanConfig {
+    /// The address and port to announce in the handshake for `target`.
+    pub fn handshake_addr<'a>(&'a self, target: &'a Target) -> (&'a str, u16) {
+        (
+            self.handshake_host.as_deref().unwrap_or(&target.ip),
+            self.handshake_port.unwrap_or(target.port)
+        )
+    }
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 256,
+            protocol_version: 760, // 1.19.2
+            protocol_candidates: vec![],
+            read_timeout: Duration::from_millis(500),
+            online_mode_probe: false,
+            host_failure_threshold: 5,
+            host_cooldown: Duration::from_secs(30),
+            rate: None,
+            adaptive_rate: None,
+            connect_jitter: Duration::ZERO,
+            jitter_seed: 0,
+            honeypot_threshold: None,
+            lossy_utf8: false,
+            filter: Filter::default(),
+            ping: false,
+            query: false,
+            auto_version: false,
+            legacy_fallback: false,
+            metrics: Arc::default(),
+            handshake_host: None,
+            handshake_port: None,
+            reset_on_close: false,
+            max_packet_size: MAX_PACKET_SIZE,
+            per_scan_deadline: Duration::from_secs(10),
+            on_result: None,
+            label: None,
+            resolver: Arc::default()
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScanError {
+    #[error("failed to connect: {0}")]
+    Connect(#[source] io::Error),
+    #[error("i/o error: {0}")]
+    Io(#[source] io::Error),
+    #[error("response is not a valid packet")]
+    MalformedResponse,
+    #[error("invalid status json: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("timed out waiting for the server")]
+    Timeout,
+    #[error("unexpected packet 0x{0:02x}")]
+    UnexpectedPacket(i32),
+    #[error("host is cooling down for another {0:?}")]
+    HostCoolingDown(Duration),
+    #[error("scan took longer than {0:?}")]
+    ScanTimeout(Duration),
+    #[error("{0}")]
+    PacketTooLarge(FrameTooLarge),
+    #[error("not a minecraft server, it talks HTTP")]
+    NotMinecraft
+}
+
+impl From<io::Error> for ScanError {
+    fn from(err: io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<FrameTooLarge>()) {
+            // checked right above, neither can fail
+            let inner = err.into_inner().unwrap().downcast::<FrameTooLarge>().unwrap();
+            return ScanError::PacketTooLarge(*inner);
+        }
+
+        match err.kind() {
+            io::ErrorKind::TimedOut => ScanError::Timeout,
+            _ => ScanError::Io(err)
+        }
+    }
+}
+
+impl ScanError {
+    /// Every value [`ScanError::kind`] can return.
+    pub const KINDS: [&'static str; 10] = ["connect", "io", "malformed_response", "invalid_json", "timeout", "unexpected_packet", "host_cooling_down", "scan_timeout", "packet_too_large", "not_minecraft"];
+
+    /// A short stable name for the kind of error, used for metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ScanError::Connect(_) => "connect",
+            ScanError::Io(_) => "io",
+            ScanError::MalformedResponse => "malformed_response",
+            ScanError::InvalidJson(_) => "invalid_json",
+            ScanError::Timeout => "timeout",
+            ScanError::UnexpectedPacket(_) => "unexpected_packet",
+            ScanError::HostCoolingDown(_) => "host_cooling_down",
+            ScanError::ScanTimeout(_) => "scan_timeout",
+            ScanError::PacketTooLarge(_) => "packet_too_large",
+            ScanError::NotMinecraft => "not_minecraft"
+        }
+    }
+
+    /// Whether trying again later could succeed (timeouts, resets), as
+    /// opposed to answers that will stay the same (refused, not minecraft).
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ScanError::Connect(err) => err.kind() != io::ErrorKind::ConnectionRefused,
+            ScanError::Io(_) | ScanError::Timeout | ScanError::HostCoolingDown(_) | ScanError::ScanTimeout(_) => true,
+            ScanError::MalformedResponse | ScanError::InvalidJson(_) | ScanError::UnexpectedPacket(_) | ScanError::PacketTooLarge(_) | ScanError::NotMinecraft => false
+        }
+    }
+
+    /// Whether the error means the host refused or dropped the connection,
+    /// as opposed to talking something we dont understand.
+    pub fn is_connection_failure(&self) -> bool {
+        matches!(self, ScanError::Connect(_) | ScanError::Io(_))
+    }
+}
+
+/// Most protocol candidates tried per target, every one is a full scan.
+pub const MAX_PROTOCOL_CANDIDATES: usize = 4;
+
+// a breather between candidates, back to back connects look like a flood
+const CANDIDATE_DELAY: Duration = Duration::from_millis(50);
+
+/// Bumped whenever fields of [`ScanResult`] change meaning or go away.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Everything we found out about a server, every output format is written
+/// from this.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScanResult {
+    /// [`SCHEMA_VERSION`] of the code that wrote it.
+    pub schema_version: u32,
+    pub ip: String,
+    pub port: u16,
+    /// Unix time in seconds when the scan finished.
+    pub timestamp: u64,
+    /// Whether the server passed `ScanConfig::filter`.
+    pub matched: bool,
+    pub motd: MOTD,
+    /// Time from sending the status request to having the whole response.
+    pub latency_ms: u64,
+    /// Round trip of the status ping, when enabled and answered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ping_ms: Option<u64>,
+    /// Bytes read from the status connection.
+    pub bytes_read: u64,
+    /// Set when `auto_version` scanned again with the server's protocol.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reprobed_protocol: Option<i32>,
+    /// Which of `ScanConfig::protocol_candidates` got this response.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidate_protocol: Option<i32>,
+    /// `motd.version.name` without formatting codes, for comparing versions.
+    pub version_name_clean: String,
+    /// Only set for servers whose sample is the real player list, see [`MOTD::sample_reliable`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub players_online_names: Option<Vec<String>>,
+    pub sample_reliable: bool,
+    /// `None` when the login probe is disabled or inconclusive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub online_mode: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub login: Option<LoginProbe>,
+    /// Full stat from the query protocol, when enabled and answered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<QueryResponse>,
+    /// TTL of the SYN-ACK masscan saw, when the target came from masscan.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u8>,
+    /// How many hops away the host probably is, see [`estimate_hops`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hops: Option<u8>,
+    /// Where the favicon was saved, relative to the output directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favicon_path: Option<PathBuf>,
+    /// Answered the pre-1.7 ping, see [`legacy::legacy_ping`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub legacy: bool,
+    /// The status was not valid UTF-8, some characters were replaced.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub lossy_utf8: bool,
+    /// Several ports of this host returned the very same status.
+    #[serde(default)]
+    pub likely_honeypot: bool,
+    /// `ScanConfig::label` of the scan that found it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub campaign: Option<String>,
+    /// Hash of the raw status response, used to spot identical answers.
+    #[serde(skip)]
+    pub status_hash: u64
+}
+
+impl ScanResult {
+    /// A result with just the status, the follow-up probes fill in the rest.
+    pub(crate) fn new(target: &Target, motd: MOTD, matched: bool, latency: Duration) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            ip: target.ip.clone(),
+            port: target.port,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            matched,
+            latency_ms: latency.as_millis() as u64,
+            ping_ms: None,
+            bytes_read: 0,
+            reprobed_protocol: None,
+            candidate_protocol: None,
+            version_name_clean: strip_formatting(&motd.version.name),
+            players_online_names: motd.online_player_names(),
+            sample_reliable: motd.sample_reliable(),
+            motd,
+            online_mode: None,
+            login: None,
+            query: None,
+            ttl: None,
+            hops: None,
+            favicon_path: None,
+            legacy: false,
+            lossy_utf8: false,
+            likely_honeypot: false,
+            campaign: None,
+            status_hash: 0
+        }
+    }
+
+    /// Records the ttl masscan saw, ignoring values no packet can have
+    /// (masscan writes 0 or negative numbers when it has none).
+    pub fn set_ttl(&mut self, ttl: i16) {
+        self.ttl = u8::try_from(ttl).ok().filter(|&ttl| ttl > 0);
+        self.hops = self.ttl.map(estimate_hops);
+    }
+}
+
+/// Guesses the hop count from a received ttl, assuming the host started at
+/// the closest common initial ttl above it (64 on Linux and macOS, 128 on
+/// Windows, 255 on network gear). Off when hosts use something else.
+pub fn estimate_hops(ttl: u8) -> u8 {
+    let initial = [64, 128, 255].into_iter().find(|&initial| ttl <= initial).unwrap_or(255);
+    initial - ttl
+}
+
+/// Scans every target and yields results as they complete.
+///
+/// Targets go through the stages of the pipeline (connect, handshake + status
+/// request, read, parse) with at most `config.concurrency` of them in flight,
+/// so results come out in completion order rather than input order.
+pub fn scan_stream(targets: impl IntoIterator<Item = Target>, config: ScanConfig) -> impl Stream<Item = Result<ScanResult, ScanError>> {
+    scan_outcomes(targets, config).map(|(_, result)| result)
+}
+
+/// Same as [`scan_stream`], but every result comes with its target so
+/// failures can be told apart.
+pub fn scan_outcomes(targets: impl IntoIterator<Item = Target>, config: ScanConfig) -> impl Stream<Item = (Target, Result<ScanResult, ScanError>)> {
+    let concurrency = config.concurrency.max(1);
+    let pipeline = Arc::new(Pipeline {
+        cooldown: HostCooldown::new(config.host_failure_threshold, config.host_cooldown),
+        honeypots: config.honeypot_threshold.map(HoneypotDetector::new),
+        rate: config.rate.map(|rate| RateLimiter::new(rate, config.adaptive_rate)),
+        config
+    });
+
+    futures::stream::iter(targets)
+        .enumerate()
+        .map(move |(index, target)| {
+            let pipeline = pipeline.clone();
+            async move {
+                let result = pipeline.scan(index, &target).await;
+                (target, result)
+            }
+        })
+        .buffer_unordered(concurrency)
+}
+
+/// State shared by every target of a scan.
+struct Pipeline {
+    config: ScanConfig,
+    cooldown: HostCooldown,
+    honeypots: Option<HoneypotDetector>,
+    rate: Option<RateLimiter>
+}
+
+impl Pipeline {
+    async fn scan(&self, index: usize, target: &Target) -> Result<ScanResult, ScanError> {
+        let (config, metrics) = (&self.config, &self.config.metrics);
+
+        if let Some(remaining) = self.cooldown.remaining(&target.ip) {
+            debug!("Skipping {}:{}, host is cooling down", target.ip, target.port);
+            let err = ScanError::HostCoolingDown(remaining);
+            metrics.record_error(&err);
+            metrics.targets_completed.fetch_add(1, Ordering::Relaxed);
+            return Err(err);
+        }
+
+        if !config.connect_jitter.is_zero() {
+            let mut rng = StdRng::seed_from_u64(config.jitter_seed.wrapping_add(index as u64));
+            sleep(rng.gen_range(Duration::ZERO..=config.connect_jitter)).await;
+        }
+
+        if let Some(rate) = &self.rate {
+            rate.acquire().await;
+        }
+
+        metrics.in_flight.fetch_add(1, Ordering::Relaxed);
+        let mut result = scan_within_deadline::<TcpStream>(target, config).await;
+        metrics.in_flight.fetch_sub(1, Ordering::Relaxed);
+        metrics.targets_completed.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(rate) = &self.rate {
+            // refused or not minecraft is an answer, timeouts and resets hint at congestion
+            rate.record(result.as_ref().err().is_some_and(ScanError::is_transient));
+        }
+
+        match &mut result {
+            Ok(result) => {
+                metrics.bytes_read.fetch_add(result.bytes_read, Ordering::Relaxed);
+                metrics.record_latency(result.latency_ms);
+                if result.matched {
+                    metrics.matches.fetch_add(1, Ordering::Relaxed);
+                }
+
+                self.cooldown.record_success(&target.ip);
+                if let Some(honeypots) = &self.honeypots {
+                    result.likely_honeypot = honeypots.observe(&target.ip, result.status_hash);
+                }
+
+                if let Some(hook) = &config.on_result {
+                    hook.call(result);
+                }
+            },
+            Err(err) => {
+                metrics.record_error(err);
+                debug!("{}:{} failed: {}", target.ip, target.port, err);
+                if err.is_connection_failure() {
+                    self.cooldown.record_failure(&target.ip);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Default for [`ScanConfig::max_packet_size`], the largest packet the
+/// protocol allows (a 3 byte length). Favicons are only ~8KB, but some
+/// servers put thousands of players into the sample.
+pub const MAX_PACKET_SIZE: usize = 0x1FFFFF; // 2MB
+
+/// Scans a single target, giving up after `config.per_scan_deadline` no
+/// matter how far it got.
+pub async fn perform_scan(target: &Target, config: &ScanConfig) -> Result<ScanResult, ScanError> {
+    perform_scan_with::<TcpStream>(target, config).await
+}
+
+/// [`perform_scan`] over another [`Transport`] than TCP.
+pub async fn perform_scan_with<T: Transport>(target: &Target, config: &ScanConfig) -> Result<ScanResult, ScanError> {
+    let result = scan_within_deadline::<T>(target, config).await?;
+
+    if let Some(hook) = &config.on_result {
+        hook.call(&result);
+    }
+
+    Ok(result)
+}
+
+async fn scan_within_deadline<T: Transport>(target: &Target, config: &ScanConfig) -> Result<ScanResult, ScanError> {
+    let mut result = timeout(config.per_scan_deadline, scan_auto_version::<T>(target, config))
+        .await
+        .map_err(|_| ScanError::ScanTimeout(config.per_scan_deadline))??;
+
+    result.campaign = config.label.clone();
+    Ok(result)
+}
+
+async fn scan_auto_version<T: Transport>(target: &Target, config: &ScanConfig) -> Result<ScanResult, ScanError> {
+    let single = config.protocol_candidates.first().copied().unwrap_or(config.protocol_version);
+    let first = match config.protocol_candidates.len() > 1 {
+        true => status_candidates::<T>(target, config).await,
+        false => status_once::<T>(target, config, single).await
+    };
+
+    let (result, stream) = match first {
+        Ok(answered) => answered,
+        // pre-1.7 servers kick or ignore the handshake, but they did accept the connection
+        Err(err) if config.legacy_fallback && !matches!(err, ScanError::Connect(_) | ScanError::NotMinecraft) => {
+            debug!("{}:{} did not answer the status ({}), trying the legacy ping", target.ip, target.port, err);
+            return legacy::legacy_ping::<T>(target, config).await.map_err(|_| err);
+        },
+        Err(err) => return Err(err)
+    };
+
+    let server_protocol = result.motd.version.protocol;
+    let announced = result.candidate_protocol.unwrap_or(single);
+    // 0 and negative numbers (-1 from proxies) are not real protocols
+    if !config.auto_version || server_protocol <= 0 || server_protocol == announced {
+        return Ok(follow_up(target, config, result, stream, announced).await);
+    }
+
+    // the server told us what it speaks, ask once more in its own version.
+    // never more than once, the answer could report yet another protocol
+    debug!("{}:{} speaks protocol {}, probing again with it", target.ip, target.port, server_protocol);
+    match status_once::<T>(target, config, server_protocol).await {
+        Ok((mut reprobed, reprobed_stream)) => {
+            reprobed.reprobed_protocol = Some(server_protocol);
+            Ok(follow_up(target, config, reprobed, reprobed_stream, server_protocol).await)
+        },
+        Err(err) => {
+            debug!("{}:{} re-probe failed, keeping the first response: {}", target.ip, target.port, err);
+            Ok(follow_up(target, config, result, stream, announced).await)
+        }
+    }
+}
+
+/// Asks for the status once per candidate protocol and keeps the richest
+/// response, the earlier candidate wins a tie. Only the status, the
+/// follow-up probes run once on the winner.
+async fn status_candidates<T: Transport>(target: &Target, config: &ScanConfig) -> Result<(ScanResult, FramedReader<T>), ScanError> {
+    let mut best: Option<(ScanResult, FramedReader<T>)> = None;
+    let mut last_err = None;
+
+    for (i, &protocol) in config.protocol_candidates.iter().take(MAX_PROTOCOL_CANDIDATES).enumerate() {
+        if i > 0 {
+            tokio::time::sleep(CANDIDATE_DELAY).await;
+        }
+
+        match status_once::<T>(target, config, protocol).await {
+            Ok((mut result, stream)) => {
+                result.candidate_protocol = Some(protocol);
+                // the losing connection is dropped (closed) right here
+                if best.as_ref().is_none_or(|(best, _)| richness(&result.motd) > richness(&best.motd)) {
+                    best = Some((result, stream));
+                }
+            },
+            // nobody listening (or not minecraft), the other protocols wont change that
+            Err(err @ (ScanError::Connect(_) | ScanError::NotMinecraft)) => return best.ok_or(err),
+            Err(err) => {
+                debug!("{}:{} failed with protocol {}: {}", target.ip, target.port, protocol, err);
+                last_err = Some(err);
+            }
+        }
+    }
+
+    best.ok_or_else(|| last_err.unwrap_or(ScanError::MalformedResponse))
+}
+
+/// How much a status tells us, some servers leave out parts depending on
+/// the protocol we announce.
+fn richness(motd: &MOTD) -> usize {
+    [
+        !motd.description.plain_text().is_empty(),
+        !motd.version.name.is_empty(),
+        motd.version.protocol > 0,
+        motd.players.max > 0,
+        motd.players.sample.as_ref().is_some_and(|sample| !sample.is_empty()),
+        motd.favicon.is_some(),
+        motd.enforces_secure_chat.is_some(),
+        motd.previews_chat.is_some()
+    ].into_iter().filter(|&present| present).count()
+}
+
+/// Just the status, handing back the still open connection so the ping can
+/// follow on it.
+async fn status_once<T: Transport>(target: &Target, config: &ScanConfig, protocol_version: i32) -> Result<(ScanResult, FramedReader<T>), ScanError> {
+    info!("Scanning {}:{}", target.ip, target.port);
+
+    let stream = T::connect(target, config).await?;
+    let mut stream = FramedReader::new(stream, config.read_timeout);
+
+    // Send the handsake and the request packet (0x00 = status request) in one
+    // write, some servers only answer when both arrive in the same segment and
+    // it saves a sendto per scan (two syscalls down to one)
+    let (handshake_host, handshake_port) = config.handshake_addr(target);
+    let mut request = build_handshake(protocol_version, handshake_host, handshake_port, STATE_STATUS);
+    request.extend_from_slice(&Packet::new(0x00, Bytes::new()).encode());
+    stream.get_mut().write_all(&request).await?;
+
+    // Read the response, just the one packet, nothing after it
+    let sent = Instant::now();
+    if looks_like_http(stream.peek(HTTP_PREFIX_LEN).await?) {
+        return Err(ScanError::NotMinecraft);
+    }
+    let response = stream.read_packet(config.max_packet_size).await?;
+    let latency = sent.elapsed();
+
+    let mut hasher = DefaultHasher::new();
+    response.hash(&mut hasher);
+    let status_hash = hasher.finish();
+
+    let Status { motd, lossy_utf8 } = parse_status_packet(response, config.lossy_utf8)?;
+    let matched = config.filter.matches(&motd);
+
+    let result = ScanResult {
+        bytes_read: stream.bytes_read(),
+        lossy_utf8,
+        status_hash,
+        ..ScanResult::new(target, motd, matched, latency)
+    };
+    Ok((result, stream))
+}
+
+/// Ping, query and login probe, once per scan on the status that was kept.
+/// They only fill in what they find, a failed probe does not fail the scan.
+async fn follow_up<T: Transport>(target: &Target, config: &ScanConfig, mut result: ScanResult, mut stream: FramedReader<T>, protocol_version: i32) -> ScanResult {
+    let (ip, port) = (target.ip.as_str(), target.port);
+
+    // everything after the status costs extra traffic, only spend it on servers we keep
+    if result.matched && config.ping {
+        match ping(&mut stream).await {
+            Ok(ping) => result.ping_ms = Some(ping.as_millis() as u64),
+            Err(err) => debug!("{}:{} did not answer the ping: {}", ip, port, err)
+        }
+        result.bytes_read = stream.bytes_read();
+    }
+
+    transport::close(stream.get_mut(), config).await; // shutdown so we dont have to wait for too long
+    drop(stream);
+
+    if !result.matched {
+        return result;
+    }
+
+    if config.query {
+        match query::query(target, config).await {
+            Ok(query) => result.query = Some(query),
+            Err(err) => debug!("{}:{} did not answer the query: {}", ip, port, err)
+        }
+    }
+
+    // the status tells us which protocol the server speaks, so log in with that
+    if config.online_mode_probe {
+        let protocol = if result.motd.version.protocol > 0 { result.motd.version.protocol } else { protocol_version };
+        match login::probe_login::<T>(target, protocol, config).await {
+            Ok(probe) => {
+                result.online_mode = probe.online_mode;
+                result.login = Some(probe);
+            },
+            Err(err) => debug!("{}:{} login probe failed: {}", ip, port, err)
+        }
+    }
+
+    result
+}
+
+// enough to tell "HTTP/" and the methods apart, every status is longer than this
+const HTTP_PREFIX_LEN: usize = 5;
+
+/// Web servers (and some honeypots) answer the handshake with an HTTP
+/// response. A status can never start like this, a single byte length
+/// would have to be followed by the 0x00 packet id.
+fn looks_like_http(prefix: &[u8]) -> bool {
+    ["HTTP/", "GET ", "POST ", "HEAD ", "PUT ", "DELETE ", "OPTIONS ", "PATCH ", "CONNECT "]
+        .iter()
+        .any(|start| prefix.starts_with(&start.as_bytes()[..HTTP_PREFIX_LEN.min(start.len())]))
+}
+
+/// Sends a ping (0x01) on the status connection and waits for the matching pong.
+async fn ping(stream: &mut FramedReader<impl Transport>) -> Result<Duration, ScanError> {
+    let payload = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+
+    let sent = Instant::now();
+    stream.get_mut().write_all(&Packet::new(0x01, payload.to_be_bytes().to_vec()).encode()).await?;
+
+    let mut pong = stream.read_packet(16).await?;
+    if pong.id != 0x01 || pong.data.remaining() < 8 || pong.data.get_u64() != payload {
+        return Err(ScanError::MalformedResponse);
+    }
+
+    Ok(sent.elapsed())
+}
@@ -0,0 +1,59 @@
+use bytes::{ Buf, Bytes };
+
+use crate::{motd::MOTD, protocol::{BufExt, Packet}, scan::ScanError};
+
+pub struct Status {
+    pub motd: MOTD,
+    /// The JSON was not valid UTF-8 and had to be decoded lossily.
+    pub lossy_utf8: bool
+}
+
+/// Pulls the status JSON out of a raw status response and parses it.
+///
+/// The response is `[packet length][packet id][string length][json]`. A few
+/// broken server implementations leave out the string length and send the
+/// JSON right after the packet id, so if the standard form doesn't parse we
+/// try the rest of the packet as raw JSON before giving up.
+///
+/// With `lossy_utf8` a JSON that only fails because of invalid UTF-8 is
+/// retried with the offending bytes replaced, structural errors still fail.
+pub fn parse_status_response(mut response: Bytes, lossy_utf8: bool) -> Result<Status, ScanError> {
+    parse_status_packet(Packet::decode(&mut response)?, lossy_utf8)
+}
+
+/// Same as [`parse_status_response`] for a packet that was already read.
+pub fn parse_status_packet(packet: Packet, lossy_utf8: bool) -> Result<Status, ScanError> {
+    // a disconnect or whatever else is not worth digging for JSON in
+    if packet.id != 0x00 {
+        return Err(ScanError::UnexpectedPacket(packet.id));
+    }
+    let response = packet.data;
+
+    let error = match parse_length_prefixed(response.clone(), lossy_utf8) {
+        Ok(status) => return Ok(status),
+        Err(err) => err
+    };
+
+    parse_json(response.chunk(), lossy_utf8).map_err(|_| error)
+}
+
+fn parse_length_prefixed(mut data: Bytes, lossy_utf8: bool) -> Result<Status, ScanError> {
+    let len = data.get_vi().ok_or(ScanError::MalformedResponse)? as usize;
+    if data.remaining() < len {
+        return Err(ScanError::MalformedResponse);
+    }
+
+    parse_json(&data[..len], lossy_utf8)
+}
+
+fn parse_json(json: &[u8], lossy_utf8: bool) -> Result<Status, ScanError> {
+    match serde_json::from_slice(json) {
+        Ok(motd) => Ok(Status { motd, lossy_utf8: false }),
+        // only retry when the encoding is at fault, not the JSON itself
+        Err(_) if lossy_utf8 && std::str::from_utf8(json).is_err() => {
+            let motd = serde_json::from_str(&String::from_utf8_lossy(json))?;
+            Ok(Status { motd, lossy_utf8: true })
+        },
+        Err(err) => Err(err.into())
+    }
+}
@@ -0,0 +1,59 @@
+use std::{future::Future, net::{Ipv4Addr, Ipv6Addr, SocketAddr}, time::Duration};
+
+use socket2::SockRef;
+use tokio::{io::{AsyncRead, AsyncWrite, AsyncWriteExt}, net::{TcpStream, UdpSocket}};
+
+use crate::scan::{ScanConfig, ScanError, Target};
+
+/// What a scan talks over, a plain TCP connection unless something else
+/// (an in-memory stream in tests, a proxy, TLS) is plugged in with
+/// [`perform_scan_with`](crate::scan::perform_scan_with).
+///
+/// Every connection of a scan goes through it, the status as well as the
+/// login probe and the legacy ping. The UDP query and the bedrock ping
+/// can't go through it, they use [`connect_udp`] instead.
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send + Sized {
+    /// Opens a connection to `target`, applying whatever of `config` makes
+    /// sense for the transport. Failures should be [`ScanError::Connect`].
+    fn connect(target: &Target, config: &ScanConfig) -> impl Future<Output = Result<Self, ScanError>> + Send;
+}
+
+impl Transport for TcpStream {
+    async fn connect(target: &Target, config: &ScanConfig) -> Result<Self, ScanError> {
+        let addrs = config.resolver.resolve(&target.ip, target.port).await.map_err(ScanError::Connect)?;
+        let stream = TcpStream::connect(&addrs[..]).await.map_err(ScanError::Connect)?;
+
+        if config.reset_on_close {
+            SockRef::from(&stream).set_linger(Some(Duration::ZERO))?;
+        }
+
+        Ok(stream)
+    }
+}
+
+/// Ends a connection we are done with. With `reset_on_close` the FIN is
+/// skipped, once the peer's FIN came back the socket is in TIME_WAIT before
+/// it is dropped and the RST of `SO_LINGER(0)` never happens.
+pub(crate) async fn close(stream: &mut impl Transport, config: &ScanConfig) {
+    if !config.reset_on_close {
+        stream.shutdown().await.ok();
+    }
+}
+
+/// A UDP socket connected to `target`, resolved the same way TCP targets
+/// are. Bound to the unspecified address of the resolved family, so ipv6
+/// targets work too.
+pub async fn connect_udp(target: &Target, config: &ScanConfig) -> Result<UdpSocket, ScanError> {
+    let addrs = config.resolver.resolve(&target.ip, target.port).await.map_err(ScanError::Connect)?;
+    // resolve never hands out an empty list
+    let addr = addrs[0];
+
+    let local = match addr {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+    };
+
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(addr).await.map_err(ScanError::Connect)?;
+    Ok(socket)
+}
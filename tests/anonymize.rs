@@ -0,0 +1,53 @@
+mod common;
+
+use common::{fixture, mock_server, status_packet};
+use quickie::{anonymize::{anonymize, anonymize_bedrock, hash_ip}, bedrock::parse_pong, perform_scan, ScanConfig, Target};
+
+#[test]
+fn ip_hashes_depend_on_the_salt() {
+    assert_eq!(hash_ip("10.0.0.1", "secret"), hash_ip("10.0.0.1", "secret"));
+    assert_ne!(hash_ip("10.0.0.1", "secret"), hash_ip("10.0.0.1", "other"));
+    assert_ne!(hash_ip("10.0.0.1", "secret"), hash_ip("10.0.0.2", "secret"));
+    assert_eq!(hash_ip("10.0.0.1", "secret").len(), 32);
+}
+
+#[test]
+fn ip_hashes_are_hmacs() {
+    // RFC 4231 test case 2, cut to 16 bytes
+    assert_eq!(hash_ip("what do ya want for nothing?", "Jefe"), "5bdcc146bf60754e6a042426089575c7");
+    // salts longer than a block are hashed first
+    assert_eq!(hash_ip("10.0.0.1", &"k".repeat(100)), "6166c3fc54c8d4ce7be014f33b1b8f0a");
+
+    // salt and ip dont just run into each other
+    assert_ne!(hash_ip("c", "ab"), hash_ip("bc", "a"));
+}
+
+#[tokio::test]
+async fn anonymized_results_keep_only_aggregate_fields() {
+    let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+    let mut result = perform_scan(&target, &ScanConfig::default()).await.unwrap();
+
+    anonymize(&mut result, "secret");
+
+    assert_eq!(result.ip, hash_ip(&target.ip, "secret"));
+    assert_eq!(result.motd.description.plain_text(), "");
+    assert!(result.motd.favicon.is_none());
+    assert!(result.motd.players.sample.is_none());
+
+    assert_eq!(result.motd.version.name, "Paper 1.20.1");
+    assert_eq!(result.motd.version.protocol, 763);
+    assert_eq!((result.motd.players.online, result.motd.players.max), (1312, 500));
+}
+
+#[test]
+fn anonymized_bedrock_pongs_keep_only_aggregate_fields() {
+    let mut motd = parse_pong(&fixture("bedrock_pong.bin")).unwrap();
+
+    anonymize_bedrock(&mut motd);
+
+    assert_eq!(motd.motd, "");
+    assert_eq!((motd.sub_motd, motd.server_id), (None, None));
+    assert_eq!((motd.protocol, motd.version.as_str()), (671, "1.20.81"));
+    assert_eq!((motd.players_online, motd.players_max), (2, 10));
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::fixture;
+use quickie::{bedrock::{bedrock_scan, parse_pong}, resolve::Resolver, ScanConfig, Target};
+use tokio::net::UdpSocket;
+
+#[test]
+fn parses_a_modern_pong() {
+    let motd = parse_pong(&fixture("bedrock_pong.bin")).unwrap();
+
+    assert_eq!(motd.edition, "MCPE");
+    assert_eq!(motd.motd, "Dedicated Server");
+    assert_eq!((motd.protocol, motd.version.as_str()), (671, "1.20.81"));
+    assert_eq!((motd.players_online, motd.players_max), (2, 10));
+    assert_eq!(motd.server_id.as_deref(), Some("13253860892328930865"));
+    assert_eq!(motd.sub_motd.as_deref(), Some("Bedrock level"));
+    assert_eq!(motd.game_mode.as_deref(), Some("Survival"));
+    assert_eq!((motd.port_v4, motd.port_v6), (Some(19132), Some(19133)));
+}
+
+#[test]
+fn parses_a_pre_1_16_pong() {
+    let motd = parse_pong(&fixture("bedrock_pong_1_14.bin")).unwrap();
+
+    assert_eq!(motd.motd, "Old Server");
+    assert_eq!((motd.protocol, motd.version.as_str()), (389, "1.14.60"));
+    assert_eq!((motd.players_online, motd.players_max), (0, 20));
+
+    // the trailing `;` must not turn into an empty server id
+    assert_eq!((motd.server_id, motd.sub_motd, motd.game_mode), (None, None, None));
+    assert_eq!((motd.port_v4, motd.port_v6), (None, None));
+}
+
+#[test]
+fn rejects_truncated_pongs() {
+    let pong = fixture("bedrock_pong.bin");
+
+    // cut anywhere before the player counts, the pong is useless
+    let players = pong.windows(3).position(|window| window == b";2;").unwrap();
+    for len in 0..players {
+        assert!(parse_pong(&pong[..len]).is_err(), "{} bytes", len);
+    }
+}
+
+#[test]
+fn rejects_garbage() {
+    assert!(parse_pong(b"HTTP/1.1 400 Bad Request\r\n\r\n").is_err());
+
+    let mut pong = fixture("bedrock_pong.bin");
+    pong[20] ^= 0xff; // inside the magic
+    assert!(parse_pong(&pong).is_err());
+
+    // a string length way past the end of the datagram
+    let mut pong = fixture("bedrock_pong.bin");
+    pong[33..35].copy_from_slice(&u16::MAX.to_be_bytes());
+    assert!(parse_pong(&pong).is_err());
+}
+
+#[tokio::test]
+async fn hostnames_go_through_the_resolver() {
+    // whichever family localhost comes up as first here
+    let local = Resolver::system().resolve("localhost", 0).await.unwrap()[0];
+    let server = UdpSocket::bind(local).await.unwrap();
+    let port = server.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let mut ping = [0u8; 64];
+        let (_, client) = server.recv_from(&mut ping).await.unwrap();
+        server.send_to(&fixture("bedrock_pong.bin"), client).await.unwrap();
+    });
+
+    let target = Target { ip: "localhost".into(), port };
+    let motd = bedrock_scan(&target, &ScanConfig { label: Some("spring-2024".into()), ..Default::default() }).await.unwrap();
+    assert_eq!(motd.motd, "Dedicated Server");
+    assert_eq!(motd.campaign.as_deref(), Some("spring-2024"));
+}
@@ -0,0 +1,73 @@
+use quickie::{checkpoint::{load_finished, Checkpoint, Outcome}, Target};
+
+fn target(ip: &str) -> Target {
+    Target { ip: ip.into(), port: 25565 }
+}
+
+fn failed(kind: &str, transient: bool) -> Outcome {
+    Outcome::Failed { kind: kind.into(), transient }
+}
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("quickie-checkpoint-{}-{}.jsonl", name, std::process::id()))
+}
+
+#[tokio::test]
+async fn the_last_record_of_a_target_wins() {
+    let path = temp_path("last");
+    let checkpoint = Checkpoint::open(&path, false).await.unwrap();
+    checkpoint.record(&target("10.0.0.1"), failed("timeout", true)).await.unwrap();
+    checkpoint.record(&target("10.0.0.1"), Outcome::Matched).await.unwrap();
+    checkpoint.record(&target("10.0.0.2"), Outcome::NotMatched).await.unwrap();
+    checkpoint.record(&target("10.0.0.2"), failed("timeout", true)).await.unwrap();
+    drop(checkpoint);
+
+    let finished = load_finished(&path, true).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // the first one succeeded in the end, the second one failed in the end
+    assert!(finished.contains(&target("10.0.0.1")));
+    assert!(!finished.contains(&target("10.0.0.2")));
+}
+
+#[tokio::test]
+async fn only_transient_failures_are_retried() {
+    let path = temp_path("retry");
+    let checkpoint = Checkpoint::open(&path, false).await.unwrap();
+    checkpoint.record(&target("10.0.0.1"), failed("timeout", true)).await.unwrap();
+    checkpoint.record(&target("10.0.0.2"), failed("connection_refused", false)).await.unwrap();
+    drop(checkpoint);
+
+    let skipped = load_finished(&path, false).unwrap();
+    let retried = load_finished(&path, true).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(skipped.len(), 2);
+    assert!(!retried.contains(&target("10.0.0.1")));
+    assert!(retried.contains(&target("10.0.0.2")));
+}
+
+#[tokio::test]
+async fn resuming_after_a_crash_keeps_every_record() {
+    let path = temp_path("crash");
+    let checkpoint = Checkpoint::open(&path, false).await.unwrap();
+    checkpoint.record(&target("10.0.0.1"), Outcome::Matched).await.unwrap();
+    drop(checkpoint);
+
+    // the process died halfway through the next line
+    let mut file = std::fs::read_to_string(&path).unwrap();
+    file.push_str(r#"{"ip":"10.0.0.2","po"#);
+    std::fs::write(&path, file).unwrap();
+    assert_eq!(load_finished(&path, false).unwrap().len(), 1);
+
+    let checkpoint = Checkpoint::open(&path, true).await.unwrap();
+    checkpoint.record(&target("10.0.0.3"), Outcome::NotMatched).await.unwrap();
+    drop(checkpoint);
+
+    let finished = load_finished(&path, false).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(finished.contains(&target("10.0.0.1")));
+    assert!(!finished.contains(&target("10.0.0.2")));
+    assert!(finished.contains(&target("10.0.0.3")));
+}
@@ -0,0 +1,92 @@
+#![allow(dead_code)]
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use bytes::{BufMut, BytesMut};
+use quickie::{motd::MOTD, protocol::{BufExt, BytesMutExt, FramedReader}};
+use tokio::{io::{AsyncRead, AsyncWrite, AsyncWriteExt}, net::TcpListener};
+
+pub fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+/// A status JSON fixture, parsed.
+pub fn motd_fixture(name: &str) -> MOTD {
+    serde_json::from_slice(&fixture(name)).unwrap()
+}
+
+/// Wraps a status JSON into a status response packet.
+pub fn status_packet(json: impl AsRef<[u8]>) -> Vec<u8> {
+    let json = json.as_ref();
+
+    let mut data = BytesMut::new();
+    data.put_vi(0x00); // packet id
+    data.put_vi(json.len() as u32);
+    data.put(json);
+
+    let mut packet = BytesMut::new();
+    packet.put_vi(data.len() as u32);
+    packet.put(data);
+    packet.to_vec()
+}
+
+/// A tiny status server: answers status requests with `status` (sent as is)
+/// and echoes pings. Returns the address it listens on.
+pub async fn mock_server(status: Vec<u8>) -> SocketAddr {
+    mock_server_with(move |_| status.clone()).await
+}
+
+/// Like [`mock_server`], but the status depends on the protocol of the handshake.
+pub async fn mock_server_with(status: impl Fn(i32) -> Vec<u8> + Send + Sync + 'static) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let status = Arc::new(status);
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let status = status.clone();
+            tokio::spawn(async move { serve(stream, |protocol| status(protocol)).await });
+        }
+    });
+
+    addr
+}
+
+/// A server that answers every connection with `status`, one byte per
+/// `interval`, never going quiet for long enough to trip a read timeout.
+pub async fn dribbling_server(status: Vec<u8>, interval: Duration) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let status = Arc::new(status);
+
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let status = status.clone();
+            tokio::spawn(async move {
+                for byte in status.iter() {
+                    tokio::time::sleep(interval).await;
+                    if stream.write_all(&[*byte]).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    addr
+}
+
+/// Plays the server side of a status connection on any stream.
+pub async fn serve(stream: impl AsyncRead + AsyncWrite + Unpin, status: impl Fn(i32) -> Vec<u8>) {
+    let mut stream = FramedReader::new(stream, Duration::from_secs(5));
+    let mut protocol = 0;
+
+    while let Ok(mut packet) = stream.read_packet(0xFFFF).await {
+        match packet.id {
+            0x00 if !packet.data.is_empty() => protocol = packet.data.get_varint_i32().unwrap(), // handshake
+            0x00 => stream.get_mut().write_all(&status(protocol)).await.unwrap(),
+            0x01 => stream.get_mut().write_all(&packet.encode()).await.unwrap(), // ping, send it right back
+            _ => break
+        }
+    }
+}
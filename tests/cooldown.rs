@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use quickie::cooldown::HostCooldown;
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+/// Fails the host `threshold` times, tripping the cooldown once.
+fn trip(cooldown: &HostCooldown, ip: &str, threshold: u32) {
+    for _ in 0..threshold {
+        cooldown.record_failure(ip);
+    }
+}
+
+// how many base cooldowns the host is skipped for, rounded up since time moved on a bit
+fn multiple(cooldown: &HostCooldown, ip: &str) -> u32 {
+    let remaining = cooldown.remaining(ip).unwrap();
+    (remaining.as_secs_f64() / HOUR.as_secs_f64()).ceil() as u32
+}
+
+#[test]
+fn hosts_cool_down_at_the_threshold() {
+    let cooldown = HostCooldown::new(3, HOUR);
+
+    trip(&cooldown, "10.0.0.1", 2);
+    assert_eq!(cooldown.remaining("10.0.0.1"), None);
+
+    cooldown.record_failure("10.0.0.1");
+    assert_eq!(multiple(&cooldown, "10.0.0.1"), 1);
+    assert_eq!(cooldown.remaining("10.0.0.2"), None);
+}
+
+#[test]
+fn a_threshold_of_zero_never_cools_down() {
+    let cooldown = HostCooldown::new(0, HOUR);
+
+    trip(&cooldown, "10.0.0.1", 100);
+    assert_eq!(cooldown.remaining("10.0.0.1"), None);
+}
+
+#[test]
+fn repeat_offenders_back_off_up_to_the_cap() {
+    let cooldown = HostCooldown::new(2, HOUR);
+
+    for expected in [1, 2, 4, 8, 16, 32, 64, 64, 64] {
+        trip(&cooldown, "10.0.0.1", 2);
+        assert_eq!(multiple(&cooldown, "10.0.0.1"), expected);
+    }
+}
+
+#[test]
+fn a_success_resets_the_count() {
+    let cooldown = HostCooldown::new(3, HOUR);
+
+    trip(&cooldown, "10.0.0.1", 2);
+    cooldown.record_success("10.0.0.1");
+    trip(&cooldown, "10.0.0.1", 2);
+    assert_eq!(cooldown.remaining("10.0.0.1"), None);
+
+    // and the backoff starts over
+    cooldown.record_failure("10.0.0.1");
+    assert_eq!(multiple(&cooldown, "10.0.0.1"), 1);
+    trip(&cooldown, "10.0.0.1", 3);
+    assert_eq!(multiple(&cooldown, "10.0.0.1"), 2);
+    cooldown.record_success("10.0.0.1");
+    trip(&cooldown, "10.0.0.1", 3);
+    assert_eq!(multiple(&cooldown, "10.0.0.1"), 1);
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::{fixture, status_packet};
+use quickie::status::parse_status_response;
+
+/// What a fixture in `tests/fixtures/corpus` should parse to.
+enum Expected {
+    Status { protocol: i32, description: &'static str },
+    /// Not a status at all, the parser has to reject it.
+    Rejected
+}
+
+use Expected::*;
+
+/// Status responses modelled on what server software (and oddities) send in
+/// the wild, add captures here whenever a server breaks the parser.
+const CORPUS: &[(&str, Expected)] = &[
+    ("vanilla_1_8_9.json", Status { protocol: 47, description: "My 1.8 PvP server" }),
+    ("vanilla_1_19_2.json", Status { protocol: 760, description: "A Minecraft Server" }),
+    ("vanilla_1_20_1.json", Status { protocol: 763, description: "Vanilla SMP" }),
+    ("legacy_1_7_10.json", Status { protocol: 5, description: "A Minecraft Server" }),
+    ("paper_1_20_4.json", Status { protocol: 765, description: "Survival | Season 4" }),
+    ("forge_1_12_2.json", Status { protocol: 340, description: "Modded server" }),
+    ("forge_1_18_2.json", Status { protocol: 758, description: "Forge server" }),
+    ("bungeecord.json", Status { protocol: 763, description: "Example Network [1.8-1.20]\nNew minigame released!" }),
+    ("velocity.json", Status { protocol: -1, description: "A Velocity Server" }),
+    // kept as the string it was sent as, we dont parse JSON out of strings
+    ("double_encoded_motd.json", Status { protocol: 762, description: r#"{"text":"Encoded twice","color":"red"}"# }),
+    ("honeypot.json", Status { protocol: 760, description: "" }),
+    ("kick_message.json", Rejected)
+];
+
+#[test]
+fn parses_the_corpus() {
+    for (name, expected) in CORPUS {
+        let result = parse_status_response(status_packet(fixture(&format!("corpus/{}", name))).into(), false);
+
+        match (expected, result) {
+            (Status { protocol, description }, Ok(status)) => {
+                assert_eq!(status.motd.version.protocol, *protocol, "{}", name);
+                assert_eq!(status.motd.description.plain_text(), *description, "{}", name);
+            },
+            (Rejected, Err(_)) => {},
+            (Status { .. }, Err(err)) => panic!("{} failed to parse: {}", name, err),
+            (Rejected, Ok(_)) => panic!("{} should have been rejected", name)
+        }
+    }
+}
+
+#[test]
+fn every_fixture_is_in_the_corpus() {
+    let dir = format!("{}/tests/fixtures/corpus", env!("CARGO_MANIFEST_DIR"));
+
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let name = entry.unwrap().file_name().into_string().unwrap();
+        assert!(CORPUS.iter().any(|(fixture, _)| *fixture == name), "{} has no expected outcome", name);
+    }
+}
@@ -0,0 +1,39 @@
+use std::collections::BTreeMap;
+
+use quickie::{diff::diff_results, Target};
+use serde_json::{json, Value};
+
+fn results(results: &[Value]) -> BTreeMap<Target, Value> {
+    results.iter().map(|result| (serde_json::from_value(result.clone()).unwrap(), result.clone())).collect()
+}
+
+fn result(ip: &str, version: &str, online: u32) -> Value {
+    json!({
+        "ip": ip,
+        "port": 25565,
+        "latency_ms": 20,
+        "motd": { "version": { "name": version, "protocol": 763 }, "players": { "online": online, "max": 20 }, "description": "A Minecraft Server" }
+    })
+}
+
+#[test]
+fn diff_reports_added_removed_and_changed_fields() {
+    let old = results(&[result("1.1.1.1", "1.20.1", 3), result("2.2.2.2", "1.20.1", 0), result("3.3.3.3", "1.19.4", 1)]);
+    let new = results(&[result("1.1.1.1", "1.20.1", 3), result("3.3.3.3", "1.20.1", 5), result("4.4.4.4", "1.8.9", 0)]);
+
+    let diff = diff_results(&old, &new);
+
+    assert_eq!(diff.added, vec![Target { ip: "4.4.4.4".into(), port: 25565 }]);
+    assert_eq!(diff.removed, vec![Target { ip: "2.2.2.2".into(), port: 25565 }]);
+
+    // the latency differs on every scan and is not a change
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(serde_json::to_value(&diff.changed[0]).unwrap(), json!({
+        "ip": "3.3.3.3",
+        "port": 25565,
+        "changes": {
+            "players_online": { "old": 1, "new": 5 },
+            "version": { "old": "1.19.4", "new": "1.20.1" }
+        }
+    }));
+}
@@ -0,0 +1,33 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, Rgba, RgbaImage};
+use quickie::output::FaviconFormat;
+
+fn favicon() -> Vec<u8> {
+    let image = RgbaImage::from_fn(64, 64, |x, y| Rgba([x as u8 * 4, y as u8 * 4, 0, if x < 32 { 255 } else { 0 }]));
+    let mut png = Cursor::new(vec![]);
+    image.write_to(&mut png, ImageFormat::Png).unwrap();
+    png.into_inner()
+}
+
+#[test]
+fn favicons_convert_to_every_format() {
+    let png = favicon();
+
+    assert_eq!(FaviconFormat::Png.convert(png.clone()).unwrap(), png);
+
+    for (format, expected) in [(FaviconFormat::Webp, ImageFormat::WebP), (FaviconFormat::Jpeg, ImageFormat::Jpeg)] {
+        let converted = format.convert(png.clone()).unwrap();
+        assert_eq!(image::guess_format(&converted).unwrap(), expected);
+        assert_eq!(image::load_from_memory(&converted).unwrap().width(), 64);
+    }
+}
+
+#[test]
+fn invalid_favicons_are_not_converted() {
+    // just the PNG signature, some servers really send this
+    let broken = b"\x89PNG\r\n\x1a\n".to_vec();
+
+    assert_eq!(FaviconFormat::Png.convert(broken.clone()).unwrap(), broken);
+    assert!(FaviconFormat::Webp.convert(broken).is_err());
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::motd_fixture;
+use quickie::filter::Filter;
+use regex::Regex;
+
+#[test]
+fn empty_filter_keeps_everything() {
+    assert!(Filter::default().matches(&motd_fixture("status_components.json")));
+    assert!(Filter::default().matches(&motd_fixture("status_1_8.json")));
+}
+
+#[test]
+fn description_is_flattened_before_matching() {
+    let motd = motd_fixture("status_components.json");
+    assert_eq!(motd.description.plain_text(), "Welcome to Survival Island");
+
+    let filter = Filter { motd_regex: Some(Regex::new("(?i)survival island").unwrap()), ..Default::default() };
+    assert!(filter.matches(&motd));
+
+    let filter = Filter { motd_regex: Some(Regex::new("creative").unwrap()), ..Default::default() };
+    assert!(!filter.matches(&motd));
+}
+
+#[test]
+fn criteria_are_combined_with_and() {
+    let motd = motd_fixture("status_components.json");
+    let regex = || Some(Regex::new("Survival").unwrap());
+
+    let all_match = Filter { max_players: Some(50), protocol: Some(760), require_favicon: true, motd_regex: regex() };
+    assert!(all_match.matches(&motd));
+
+    // a single failing criterion is enough to drop the server
+    assert!(!Filter { max_players: Some(20), ..all_match.clone() }.matches(&motd));
+    assert!(!Filter { protocol: Some(47), ..all_match.clone() }.matches(&motd));
+    assert!(!Filter { motd_regex: Some(Regex::new("Skyblock").unwrap()), ..all_match.clone() }.matches(&motd));
+    assert!(!all_match.matches(&motd_fixture("status_1_8.json")));
+}
@@ -0,0 +1,25 @@
+use quickie::honeypot::HoneypotDetector;
+
+#[test]
+fn hosts_are_flagged_once_they_cross_the_threshold() {
+    let detector = HoneypotDetector::new(3);
+
+    // the first two ports are already out when the third crosses the threshold
+    assert!(!detector.observe("10.0.0.1", 1));
+    assert!(!detector.observe("10.0.0.1", 1));
+    assert!(detector.observe("10.0.0.1", 1));
+    assert!(detector.observe("10.0.0.1", 1));
+
+    // a different status on the same host and other hosts count on their own
+    assert!(!detector.observe("10.0.0.1", 2));
+    assert!(!detector.observe("10.0.0.2", 1));
+    assert!(!detector.observe("10.0.0.2", 1));
+}
+
+#[test]
+fn a_single_answer_is_never_a_honeypot() {
+    let detector = HoneypotDetector::new(0);
+
+    assert!(!detector.observe("10.0.0.1", 1));
+    assert!(detector.observe("10.0.0.1", 1));
+}
@@ -0,0 +1,54 @@
+use quickie::input::parse_masscan_binary;
+
+/// A masscan `-oB` file with a header, an open port, a closed port and an open udp port.
+fn masscan_file() -> Vec<u8> {
+    let mut file = b"masscan/1.1\ns:1700000000\n".to_vec();
+    file.resize(99, 0);
+
+    // type 1: open tcp, 12 bytes of timestamp, ip, port, reason, ttl
+    file.extend([1, 12, 0x65, 0x53, 0xf1, 0x00, 10, 0, 0, 1, 0x63, 0xdd, 0x12, 54]);
+    // type 2: closed, skipped
+    file.extend([2, 12, 0x65, 0x53, 0xf1, 0x00, 10, 0, 0, 2, 0x63, 0xdd, 0x04, 54]);
+    // type 6: open with ip protocol (17 = udp)
+    file.extend([6, 13, 0x65, 0x53, 0xf1, 0x01, 10, 0, 0, 3, 17, 0x4a, 0xbc, 0x00, 128]);
+    file
+}
+
+#[test]
+fn reads_masscan_binary_output() {
+    let entries = parse_masscan_binary(&masscan_file()).unwrap();
+
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0].ip, "10.0.0.1");
+    assert_eq!(entries[0].timestamp, "1700000000");
+    assert_eq!(entries[0].ports[0].port, 25565);
+    assert_eq!(entries[0].ports[0].proto, "tcp");
+    assert_eq!(entries[0].ports[0].reason, "syn-ack");
+    assert_eq!(entries[0].ports[0].ttl, 54);
+
+    assert_eq!(entries[1].ip, "10.0.0.3");
+    assert_eq!(entries[1].ports[0].port, 19132);
+    assert_eq!(entries[1].ports[0].proto, "udp");
+}
+
+#[test]
+fn stops_at_a_cut_off_record() {
+    let mut file = masscan_file();
+    file.truncate(file.len() - 4);
+
+    assert_eq!(parse_masscan_binary(&file).unwrap().len(), 1);
+}
+
+#[test]
+fn targets_can_be_ordered_by_host_or_port() {
+    use quickie::{input::{order_targets, TargetOrder}, Target};
+
+    let target = |ip: &str, port| Target { ip: ip.into(), port };
+    let targets = vec![target("a", 25565), target("b", 25565), target("a", 25566), target("c", 25566), target("b", 25567)];
+
+    let order = |order| order_targets(targets.clone(), order).into_iter().map(|target| format!("{}:{}", target.ip, target.port)).collect::<Vec<_>>();
+
+    assert_eq!(order(TargetOrder::HostsFirst), ["a:25565", "a:25566", "b:25565", "b:25567", "c:25566"]);
+    assert_eq!(order(TargetOrder::PortsFirst), ["a:25565", "b:25565", "a:25566", "c:25566", "b:25567"]);
+}
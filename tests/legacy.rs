@@ -0,0 +1,44 @@
+mod common;
+
+use common::fixture;
+use quickie::legacy::parse_legacy_response;
+
+/// Skips the kick packet id and length.
+fn legacy_fixture(name: &str) -> Vec<u8> {
+    fixture(name)[3..].to_vec()
+}
+
+#[test]
+fn parses_the_1_6_format() {
+    let motd = parse_legacy_response(&legacy_fixture("legacy_1_6.bin")).unwrap();
+
+    assert_eq!(motd.version.protocol, 78);
+    assert_eq!(motd.version.name, "1.6.4");
+    assert_eq!(motd.description.plain_text(), "A Minecraft Server");
+    assert_eq!((motd.players.online, motd.players.max), (3, 20));
+}
+
+#[test]
+fn parses_the_1_4_format() {
+    let motd = parse_legacy_response(&legacy_fixture("legacy_1_4.bin")).unwrap();
+
+    assert_eq!(motd.version.protocol, 51);
+    assert_eq!(motd.description.plain_text(), "Old but gold");
+    assert_eq!((motd.players.online, motd.players.max), (0, 10));
+}
+
+#[test]
+fn parses_the_beta_format() {
+    let motd = parse_legacy_response(&legacy_fixture("legacy_beta.bin")).unwrap();
+
+    // beta servers dont tell their version
+    assert_eq!(motd.version.protocol, 0);
+    assert_eq!(motd.description.plain_text(), "A Beta Server");
+    assert_eq!((motd.players.online, motd.players.max), (5, 16));
+}
+
+#[test]
+fn rejects_plain_kick_messages() {
+    let kick: Vec<u8> = "Protocol error".encode_utf16().flat_map(u16::to_be_bytes).collect();
+    assert!(parse_legacy_response(&kick).is_err());
+}
@@ -0,0 +1,226 @@
+use std::{collections::HashMap, io, pin::Pin, sync::Mutex, task::{Context, Poll}, time::Duration};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use quickie::{login::{probe_login, LoginPhase, LoginProbe, PostLogin}, protocol::{BufExt, BytesMutExt, FramedReader, Packet}, transport::Transport, ScanConfig, ScanError, Target};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf};
+
+const KICK: &str = r#"{"text":"You are not whitelisted"}"#;
+
+/// What the login server got from us, by target ip.
+#[derive(Default)]
+struct Seen {
+    /// Login Start without the name.
+    login_start: Vec<u8>,
+    acknowledged: bool,
+    plugin_response: Option<Vec<u8>>
+}
+
+static SEEN: Mutex<Option<HashMap<String, Seen>>> = Mutex::new(None);
+
+fn seen<R>(ip: &str, f: impl FnOnce(&mut Seen) -> R) -> R {
+    f(SEEN.lock().unwrap().get_or_insert_with(HashMap::default).entry(ip.to_string()).or_default())
+}
+
+/// An in-memory connection to a login server, the target ip picks how it behaves.
+struct Login(DuplexStream);
+
+impl Transport for Login {
+    async fn connect(target: &Target, _: &ScanConfig) -> Result<Self, ScanError> {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(login_server(server, target.ip.clone()));
+        Ok(Login(client))
+    }
+}
+
+impl AsyncRead for Login {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Login {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+/// Plays a server for the ip `{mode}/{test}`, the modes being
+/// - `online`: asks for encryption (and authentication)
+/// - `proxy`: asks for encryption without authentication (1.20.5+)
+/// - `offline`: accepts the login
+/// - `kick`: accepts the login and kicks us right after
+/// - `compressed`: enables compression, then accepts the login
+/// - `plugin`: sends a login plugin request, then accepts the login
+async fn login_server(stream: DuplexStream, ip: String) {
+    let mode = ip.split('/').next().unwrap().to_string();
+    let mut stream = FramedReader::new(stream, Duration::from_secs(5));
+
+    let mut handshake = stream.read_packet(0xFFFF).await.unwrap().data;
+    let protocol = handshake.get_varint_i32().unwrap();
+
+    let mut login_start = stream.read_packet(0xFFFF).await.unwrap().data;
+    login_start.get_str().unwrap();
+    seen(&ip, |seen| seen.login_start = login_start.to_vec());
+
+    let mut compressed = false;
+    match mode.as_str() {
+        "online" | "proxy" => {
+            let mut request = BytesMut::new();
+            request.put_str(""); // server id
+            request.put_vi(4);
+            request.put(&b"key!"[..]);
+            request.put_vi(4);
+            request.put(&b"toke"[..]);
+            if protocol >= 766 {
+                request.put_u8((mode == "online") as u8);
+            }
+            return send(&mut stream, 0x01, request, false).await;
+        },
+        "compressed" => {
+            let mut threshold = BytesMut::new();
+            threshold.put_vi(256);
+            send(&mut stream, 0x03, threshold, false).await;
+            compressed = true;
+        },
+        "plugin" => {
+            let mut request = BytesMut::new();
+            request.put_vi(7); // message id
+            request.put_str("velocity:player_info");
+            send(&mut stream, 0x04, request, false).await;
+
+            let response = stream.read_packet(0xFFFF).await.unwrap();
+            assert_eq!(response.id, 0x02);
+            seen(&ip, |seen| seen.plugin_response = Some(response.data.to_vec()));
+        },
+        _ => {}
+    }
+
+    // login success, what is in it does not matter to the probe
+    send(&mut stream, 0x02, BytesMut::from(&[0u8; 16][..]), compressed).await;
+
+    if protocol >= 764 {
+        let mut frame = stream.read_frame(0xFFFF).await.unwrap();
+        if compressed {
+            assert_eq!(frame.get_vi(), Some(0));
+        }
+        assert_eq!(Packet::from_frame(frame).unwrap().id, 0x03);
+        seen(&ip, |seen| seen.acknowledged = true);
+    }
+
+    let mut reason = BytesMut::new();
+    reason.put_str(KICK);
+    let (id, data) = match (mode.as_str(), protocol) {
+        // configuration disconnect
+        ("kick", 764..=765) => (0x01, reason),
+        ("kick", 766..) => (0x02, reason),
+        // play disconnect, the id is different in every version
+        ("kick", _) => (0x1a, reason),
+        // a plugin message, it moved to 0x01 when disconnect left it
+        (_, 764..=765) => (0x00, BytesMut::from(&b"\x0fminecraft:brand"[..])),
+        (_, 766..) => (0x01, BytesMut::from(&b"\x0fminecraft:brand"[..])),
+        // join game, starting with the entity id
+        _ => (0x28, BytesMut::from(&[0u8, 0, 0, 1][..]))
+    };
+    // a kick on 1.20.5 under the 1.20.2 id is just a plugin message
+    let id = if ip.ends_with("old-kick-id") { 0x01 } else { id };
+    send(&mut stream, id, data, compressed).await;
+}
+
+async fn send(stream: &mut FramedReader<DuplexStream>, id: i32, data: BytesMut, compressed: bool) {
+    let packet = match compressed {
+        false => Packet::new(id, data).encode(),
+        true => {
+            let mut inner = BytesMut::new();
+            inner.put_vi(0); // below the threshold
+            inner.put_varint_i32(id);
+            inner.put(data);
+
+            let mut packet = BytesMut::new();
+            packet.put_vi(inner.len() as u32);
+            packet.put(inner);
+            packet
+        }
+    };
+    stream.get_mut().write_all(&packet).await.unwrap();
+}
+
+async fn probe(ip: &str, protocol: i32) -> LoginProbe {
+    probe_login::<Login>(&Target { ip: ip.into(), port: 25565 }, protocol, &ScanConfig::default()).await.unwrap()
+}
+
+#[tokio::test]
+async fn login_start_matches_the_protocol() {
+    // what follows the name: nothing, signature data, signature data and
+    // uuid flags, the uuid flag and finally the uuid itself
+    for (protocol, layout) in [(758, vec![]), (759, vec![0]), (760, vec![0, 0]), (761, vec![0]), (763, vec![0]), (764, vec![0; 16]), (766, vec![0; 16])] {
+        let ip = format!("offline/layout-{}", protocol);
+        probe(&ip, protocol).await;
+        assert_eq!(seen(&ip, |seen| seen.login_start.clone()), layout, "protocol {}", protocol);
+    }
+}
+
+#[tokio::test]
+async fn encryption_means_online_mode() {
+    for protocol in [763, 764, 766] {
+        let probe = probe(&format!("online/{}", protocol), protocol).await;
+        assert_eq!(probe.online_mode, Some(true), "protocol {}", protocol);
+        assert_eq!(probe.next_phase, None);
+    }
+
+    // 1.20.5+ proxies encrypt without having us authenticate
+    assert_eq!(probe("proxy/766", 766).await.online_mode, Some(false));
+}
+
+#[tokio::test]
+async fn offline_servers_move_on_to_the_next_phase() {
+    for (protocol, phase) in [(763, LoginPhase::Play), (764, LoginPhase::Configuration), (766, LoginPhase::Configuration)] {
+        let ip = format!("offline/{}", protocol);
+        let probe = probe(&ip, protocol).await;
+
+        assert_eq!(probe.online_mode, Some(false), "protocol {}", protocol);
+        assert_eq!(probe.next_phase, Some(phase));
+        assert_eq!(probe.post_login, Some(PostLogin::Accepted));
+        // only the configuration phase waits for Login Acknowledged
+        assert_eq!(seen(&ip, |seen| seen.acknowledged), protocol >= 764);
+    }
+}
+
+#[tokio::test]
+async fn kicks_after_the_login_are_recorded() {
+    for protocol in [763, 764, 766] {
+        let probe = probe(&format!("kick/{}", protocol), protocol).await;
+        assert_eq!(probe.online_mode, Some(false), "protocol {}", protocol);
+        assert_eq!(probe.post_login, Some(PostLogin::Disconnected(Some(KICK.to_string()))), "protocol {}", protocol);
+    }
+
+    // the configuration disconnect moved to 0x02 in 1.20.5
+    assert_eq!(probe("kick/old-kick-id", 766).await.post_login, Some(PostLogin::Accepted));
+}
+
+#[tokio::test]
+async fn compression_is_followed_through() {
+    let probe = probe("compressed/764", 764).await;
+
+    assert_eq!(probe.online_mode, Some(false));
+    assert_eq!(probe.post_login, Some(PostLogin::Accepted));
+    assert!(seen("compressed/764", |seen| seen.acknowledged));
+}
+
+#[tokio::test]
+async fn plugin_requests_are_declined() {
+    let probe = probe("plugin/763", 763).await;
+    assert_eq!(probe.online_mode, Some(false));
+
+    // the message id back and "not understood"
+    let mut response = Bytes::from(seen("plugin/763", |seen| seen.plugin_response.clone()).unwrap());
+    assert_eq!(response.get_vi(), Some(7));
+    assert_eq!(response.chunk(), [0]);
+}
@@ -0,0 +1,19 @@
+use quickie::{metrics::ScanMetrics, ScanError};
+
+#[test]
+fn latency_histogram_buckets_and_covers() {
+    let metrics = ScanMetrics::default();
+    for latency in [10, 49, 50, 300, 5000] {
+        metrics.record_latency(latency);
+    }
+    metrics.record_error(&ScanError::Timeout);
+
+    let histogram = metrics.latency_histogram();
+    let lines: Vec<&str> = histogram.lines().collect();
+
+    assert_eq!(lines.len(), 7);
+    assert!(lines[0].starts_with("   <50ms        2 ") && lines[0].ends_with(" 40.0%"));
+    assert!(lines[1].ends_with(" 60.0%"));
+    assert!(lines[5].starts_with(">=1000ms        1 ") && lines[5].ends_with("100.0%"));
+    assert!(lines[6].starts_with(" timeout        1 #"));
+}
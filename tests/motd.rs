@@ -0,0 +1,40 @@
+mod common;
+
+use common::motd_fixture;
+use quickie::motd::{strip_formatting, MOTD};
+
+#[test]
+fn sample_of_1_8_servers_is_the_online_list() {
+    let motd = motd_fixture("status_1_8.json");
+
+    assert!(motd.sample_reliable());
+    assert_eq!(motd.online_player_names().unwrap(), ["Notch", "jeb_", "Dinnerbone"]);
+}
+
+#[test]
+fn sample_of_modern_servers_is_unreliable() {
+    let motd = motd_fixture("status_1_20.json");
+
+    assert!(!motd.sample_reliable());
+    assert_eq!(motd.online_player_names(), None);
+}
+
+#[test]
+fn strips_formatting_codes() {
+    assert_eq!(strip_formatting("§aPaper §71.20.1"), "Paper 1.20.1");
+    assert_eq!(strip_formatting("§l§nBold§r"), "Bold");
+    assert_eq!(strip_formatting("trailing §"), "trailing ");
+    assert_eq!(strip_formatting("1.19.2"), "1.19.2");
+}
+
+#[test]
+fn keeps_non_string_version_names() {
+    let motd: MOTD = serde_json::from_str(r#"{"version":{"name":{"text":"§cMaintenance"},"protocol":47},"players":{"max":0,"online":0},"description":{"text":""}}"#).unwrap();
+    assert_eq!(motd.version.name, r#"{"text":"§cMaintenance"}"#);
+}
+
+#[test]
+fn secure_chat_only_on_newer_servers() {
+    assert_eq!(motd_fixture("status_1_20.json").enforces_secure_chat, Some(true));
+    assert_eq!(motd_fixture("status_1_8.json").enforces_secure_chat, None);
+}
@@ -0,0 +1,279 @@
+mod common;
+
+use std::sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex};
+
+use common::{fixture, mock_server, status_packet};
+use quickie::{output::{OutputSink, ResultWriter}, perform_scan, ScanConfig, ScanResult, Target};
+
+// ENOSPC
+const DISK_FULL: i32 = 28;
+
+/// Fails every write, like a full disk.
+#[derive(Clone, Default)]
+struct BrokenSink {
+    attempts: Arc<AtomicUsize>
+}
+
+impl OutputSink for BrokenSink {
+    async fn write(&self, _: &ScanResult) -> anyhow::Result<()> {
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+        Err(std::io::Error::from_raw_os_error(DISK_FULL).into())
+    }
+
+    async fn finish(&self) -> anyhow::Result<()> {
+        Err(std::io::Error::from_raw_os_error(DISK_FULL).into())
+    }
+}
+
+/// Rejects every result, the storage itself is fine.
+#[derive(Clone, Default)]
+struct PickySink {
+    attempts: Arc<AtomicUsize>
+}
+
+impl OutputSink for PickySink {
+    async fn write(&self, _: &ScanResult) -> anyhow::Result<()> {
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+        anyhow::bail!("this result makes no sense")
+    }
+
+    async fn finish(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Collects what the writer prints instead of writing.
+#[derive(Clone, Default)]
+struct Printed(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for Printed {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn broken_output_stops_being_written_to() {
+    let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+    let json = serde_json::to_string(&perform_scan(&target, &ScanConfig::default()).await.unwrap()).unwrap();
+
+    let sink = BrokenSink::default();
+    let printed = Printed::default();
+    let writer = ResultWriter::spawn_with_fallback(sink.clone(), 4, printed.clone());
+    for _ in 0..20 {
+        writer.send(serde_json::from_str(&json).unwrap()).await;
+    }
+
+    // the results went to stdout, so finishing is not an error either
+    writer.finish().await.unwrap();
+    assert!((8..8 + 4).contains(&sink.attempts.load(Ordering::Relaxed)));
+
+    // none got lost, not even the ones whose write failed
+    let printed = String::from_utf8(printed.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(printed.lines().count(), 20);
+    assert!(printed.lines().all(|line| serde_json::from_str::<ScanResult>(line).is_ok()));
+}
+
+#[tokio::test]
+async fn bad_results_dont_count_as_broken_output() {
+    let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+    let result = perform_scan(&target, &ScanConfig::default()).await.unwrap();
+
+    let sink = PickySink::default();
+    let printed = Printed::default();
+    let writer = ResultWriter::spawn_with_fallback(sink.clone(), 4, printed.clone());
+    for _ in 0..20 {
+        writer.send(result.clone()).await;
+    }
+    writer.finish().await.unwrap();
+
+    // every write was tried, each rejected one printed
+    assert_eq!(sink.attempts.load(Ordering::Relaxed), 20);
+    assert_eq!(printed.0.lock().unwrap().split(|&byte| byte == b'\n').filter(|line| !line.is_empty()).count(), 20);
+}
+
+#[tokio::test]
+async fn every_port_of_a_host_gets_its_own_file() {
+    use quickie::output::{FileSink, OutputConfig};
+
+    let dir = std::env::temp_dir().join(format!("quickie-output-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = Arc::new(OutputConfig { dir: dir.clone(), ..Default::default() });
+    let writer = ResultWriter::spawn(FileSink::new(output), 4);
+
+    let mut ports = vec![];
+    for _ in 0..2 {
+        let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+        let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+        writer.send(perform_scan(&target, &ScanConfig::default()).await.unwrap()).await;
+        ports.push(addr.port());
+    }
+    writer.finish().await.unwrap();
+
+    let saved = quickie::input::saved_targets(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    ports.sort();
+    assert_eq!(saved.iter().map(|target| target.port).collect::<Vec<_>>(), ports);
+}
+
+#[tokio::test]
+async fn malformed_favicons_dont_take_down_the_writer() {
+    use quickie::output::{FileSink, OutputConfig};
+
+    let dir = std::env::temp_dir().join(format!("quickie-favicon-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = Arc::new(OutputConfig { dir: dir.clone(), ..Default::default() });
+    let writer = ResultWriter::spawn_with_fallback(FileSink::new(output), 1, std::io::sink());
+
+    // short and with a multi-byte character where the prefix would end
+    for favicon in ["data:", "data:image/png;base64é,AAAA", "data:image/png;base64,iVBORw0KGgo="] {
+        let status = format!(r#"{{"version":{{"name":"1.20.1","protocol":763}},"players":{{"max":20,"online":0}},"description":"","favicon":"{}"}}"#, favicon);
+        let addr = mock_server(status_packet(status)).await;
+        let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+        writer.send(perform_scan(&target, &ScanConfig::default()).await.unwrap()).await;
+    }
+    writer.finish().await.unwrap();
+
+    // every result is saved, only the valid favicon next to it
+    let saved = quickie::input::saved_targets(&dir).unwrap();
+    let favicons = std::fs::read_dir(&dir).unwrap().filter(|entry| entry.as_ref().unwrap().path().extension().is_some_and(|extension| extension == "png")).count();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(saved.len(), 3);
+    assert_eq!(favicons, 1);
+}
+
+#[tokio::test]
+async fn sqlite_keeps_the_latest_result_per_port() {
+    use quickie::output::SqliteSink;
+
+    let path = std::env::temp_dir().join(format!("quickie-sqlite-{}.db", std::process::id()));
+    let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+    let scanned = perform_scan(&target, &ScanConfig::default()).await.unwrap();
+
+    // two ports on one host, the first one scanned again with more players
+    let mut other_port = scanned.clone();
+    other_port.port += 1;
+    let mut rescanned = scanned.clone();
+    rescanned.motd.players.online = 1400;
+    rescanned.timestamp += 60;
+
+    // far fewer than a batch, finish has to flush them
+    let writer = ResultWriter::spawn(SqliteSink::open(&path).unwrap(), 1);
+    for result in [scanned.clone(), other_port, rescanned] {
+        writer.send(result).await;
+    }
+    writer.finish().await.unwrap();
+
+    let connection = rusqlite::Connection::open(&path).unwrap();
+    let servers: Vec<(String, u64, u64)> = connection.prepare("SELECT ip, first_seen, last_seen FROM servers").unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+        .collect::<Result<_, _>>().unwrap();
+    let ports: Vec<(u16, u32, String)> = connection.prepare("SELECT port, players_online, version FROM ports ORDER BY port").unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+        .collect::<Result<_, _>>().unwrap();
+    drop(connection);
+    for suffix in ["", "-wal", "-shm"] {
+        std::fs::remove_file(format!("{}{}", path.display(), suffix)).ok();
+    }
+
+    assert_eq!(servers, vec![(target.ip.clone(), scanned.timestamp, scanned.timestamp + 60)]);
+    assert_eq!(ports, vec![(target.port, 1400, "Paper 1.20.1".to_string()), (target.port + 1, 1312, "Paper 1.20.1".to_string())]);
+}
+
+#[tokio::test]
+async fn the_index_keeps_servers_of_earlier_runs() {
+    use quickie::output::{FileSink, IndexEntry, OutputConfig};
+
+    let dir = std::env::temp_dir().join(format!("quickie-index-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = Arc::new(OutputConfig { dir: dir.clone(), ..Default::default() });
+
+    // two runs, one server each
+    for _ in 0..2 {
+        let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+        let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+        let writer = ResultWriter::spawn(FileSink::new(output.clone()), 1);
+        writer.send(perform_scan(&target, &ScanConfig::default()).await.unwrap()).await;
+        writer.finish().await.unwrap();
+    }
+
+    let index: Vec<IndexEntry> = serde_json::from_str(&std::fs::read_to_string(dir.join("index.json")).unwrap()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(index.len(), 2);
+}
+
+#[tokio::test]
+async fn rechecks_flag_offline_servers_in_the_index() {
+    use quickie::output::{FileSink, IndexEntry, OutputConfig};
+
+    let dir = std::env::temp_dir().join(format!("quickie-offline-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = Arc::new(OutputConfig { dir: dir.clone(), ..Default::default() });
+
+    let mut targets = vec![];
+    for _ in 0..2 {
+        let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+        targets.push(Target { ip: addr.ip().to_string(), port: addr.port() });
+    }
+
+    let writer = ResultWriter::spawn(FileSink::new(output.clone()), 1);
+    for target in &targets {
+        writer.send(perform_scan(target, &ScanConfig::default()).await.unwrap()).await;
+    }
+    writer.finish().await.unwrap();
+
+    // a recheck where only the first one is still up
+    let writer = ResultWriter::spawn(FileSink::new(output), 1);
+    writer.send(perform_scan(&targets[0], &ScanConfig::default()).await.unwrap()).await;
+    writer.sink().mark_offline(&targets[1]);
+    writer.finish().await.unwrap();
+
+    let index: Vec<IndexEntry> = serde_json::from_str(&std::fs::read_to_string(dir.join("index.json")).unwrap()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let offline = |target: &Target| index.iter().find(|entry| entry.port == target.port).map(|entry| entry.offline);
+    assert_eq!(index.len(), 2);
+    assert_eq!(offline(&targets[0]), Some(false));
+    assert_eq!(offline(&targets[1]), Some(true));
+}
+
+#[tokio::test]
+async fn failed_sqlite_batches_are_printed_whole() {
+    use quickie::output::SqliteSink;
+
+    let path = std::env::temp_dir().join(format!("quickie-sqlite-broken-{}.db", std::process::id()));
+    let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+    let scanned = perform_scan(&target, &ScanConfig::default()).await.unwrap();
+
+    // every insert fails from here on
+    let sink = SqliteSink::open(&path).unwrap();
+    rusqlite::Connection::open(&path).unwrap().execute_batch("DROP TABLE ports").unwrap();
+
+    // one full batch failing in write, the rest failing in finish
+    let printed = Printed::default();
+    let writer = ResultWriter::spawn_with_fallback(sink, 1, printed.clone());
+    for port in 0..300 {
+        let mut result = scanned.clone();
+        result.port = port;
+        writer.send(result).await;
+    }
+    let finished = writer.finish().await;
+    for suffix in ["", "-wal", "-shm"] {
+        std::fs::remove_file(format!("{}{}", path.display(), suffix)).ok();
+    }
+
+    assert!(finished.is_err());
+    let printed = String::from_utf8(printed.0.lock().unwrap().clone()).unwrap();
+    let mut ports: Vec<u16> = printed.lines().map(|line| serde_json::from_str::<ScanResult>(line).unwrap().port).collect();
+    ports.sort();
+    assert_eq!(ports, (0..300).collect::<Vec<_>>());
+}
@@ -0,0 +1,18 @@
+use std::time::Duration;
+
+use quickie::{preflight::{preflight, PreflightError}, resolve::Resolver, Target};
+
+#[tokio::test]
+async fn udp_targets_only_get_resolved() {
+    // nothing listens on TCP here, like a bedrock server
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let target = Target { ip: "127.0.0.1".into(), port: listener.local_addr().unwrap().port() };
+    drop(listener);
+    let targets = [target];
+
+    let tcp = preflight(&targets, 1, Duration::from_secs(1), &Resolver::system(), true).await;
+    assert!(matches!(tcp, Err(PreflightError::Unreachable(1))));
+
+    let udp = preflight(&targets, 1, Duration::from_secs(1), &Resolver::system(), false).await.unwrap();
+    assert_eq!((udp.hosts, udp.sampled), (1, 0));
+}
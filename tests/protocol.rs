@@ -0,0 +1,73 @@
+use bytes::{Buf, Bytes, BytesMut};
+use quickie::{motd::MOTD, protocol::{build_handshake, BufExt, BytesMutExt, Packet, MAX_HANDSHAKE_HOST, STATE_STATUS}};
+
+#[test]
+fn negative_varint_round_trips() {
+    let mut buffer = BytesMut::new();
+    buffer.put_varint_i32(-1);
+    assert_eq!(&buffer[..], [0xff, 0xff, 0xff, 0xff, 0x0f]);
+
+    let mut canonical = Bytes::from_static(&[0xff, 0xff, 0xff, 0xff, 0x0f]);
+    assert_eq!(canonical.get_varint_i32(), Some(-1));
+}
+
+#[test]
+fn negative_protocol_in_status() {
+    let motd: MOTD = serde_json::from_str(r#"{"version":{"name":"Velocity","protocol":-1},"players":{"max":0,"online":0},"description":""}"#).unwrap();
+    assert_eq!(motd.version.protocol, -1);
+}
+
+#[test]
+fn long_handshake_hosts_are_truncated() {
+    let mut handshake = build_handshake(760, &"a".repeat(300), 25565, STATE_STATUS).freeze();
+
+    handshake.get_vi().unwrap(); // packet length
+    assert_eq!(handshake.get_vi(), Some(0x00));
+    assert_eq!(handshake.get_varint_i32(), Some(760));
+    assert_eq!(handshake.get_str().unwrap(), "a".repeat(MAX_HANDSHAKE_HOST));
+    assert_eq!(handshake.get_u16(), 25565);
+}
+
+#[test]
+fn packets_round_trip() {
+    let packet = Packet::new(-1, Bytes::from_static(b"hello"));
+
+    let mut encoded = packet.encode().freeze();
+    assert_eq!(encoded.len(), 1 + 5 + 5);
+    assert_eq!(Packet::decode(&mut encoded).unwrap(), packet);
+    assert!(encoded.is_empty());
+}
+
+#[test]
+fn truncated_packets_are_rejected() {
+    let mut encoded = Packet::new(0x00, Bytes::from_static(b"hello")).encode().freeze();
+    encoded.truncate(4);
+
+    assert!(Packet::decode(&mut encoded).is_err());
+}
+
+#[test]
+fn varint_lengths_at_every_boundary() {
+    use quickie::protocol::MAX_VARINT_LEN;
+
+    for (value, len) in [(0, 1), (127, 1), (128, 2), (16_383, 2), (16_384, 3), (2_097_151, 3), (2_097_152, 4), (268_435_455, 4), (268_435_456, 5), (u32::MAX, 5)] {
+        let mut buffer = BytesMut::new();
+        buffer.put_vi(value);
+        assert_eq!(buffer.len(), len, "{} should take {} bytes", value, len);
+
+        let mut encoded = buffer.freeze();
+        assert_eq!(encoded.get_vi(), Some(value));
+        assert!(!encoded.has_remaining());
+    }
+
+    let mut max = BytesMut::new();
+    max.put_vi(u32::MAX);
+    assert_eq!(max.len(), MAX_VARINT_LEN);
+    assert_eq!(&max[..], [0xff, 0xff, 0xff, 0xff, 0x0f]);
+}
+
+#[test]
+fn varints_longer_than_five_bytes_are_rejected() {
+    let mut six = Bytes::from_static(&[0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+    assert_eq!(six.get_vi(), None);
+}
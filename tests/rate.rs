@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+use quickie::rate::{RateLimiter, MAX_RATE_FACTOR};
+
+#[tokio::test]
+async fn the_bucket_holds_scans_to_the_rate() {
+    let limiter = RateLimiter::new(100.0, None);
+
+    let start = Instant::now();
+    for _ in 0..30 {
+        limiter.acquire().await;
+    }
+
+    // 10 go right away (the burst), the other 20 at 100 per second
+    assert!(start.elapsed() >= Duration::from_millis(190), "took {:?}", start.elapsed());
+}
+
+#[test]
+fn the_adaptive_rate_backs_off_and_recovers() {
+    let limiter = RateLimiter::new(100.0, Some(0.05));
+
+    for _ in 0..200 {
+        limiter.record(true);
+    }
+    let backed_off = limiter.rate();
+    assert!(backed_off < 100.0);
+
+    // a clean stretch speeds it up again, but never past the ceiling
+    for _ in 0..10_000 {
+        limiter.record(false);
+    }
+    assert!(limiter.rate() > backed_off);
+    assert!(limiter.rate() <= 100.0 * MAX_RATE_FACTOR);
+}
+
+#[test]
+fn a_fixed_rate_stays_fixed() {
+    let limiter = RateLimiter::new(100.0, None);
+    for _ in 0..200 {
+        limiter.record(true);
+    }
+    assert_eq!(limiter.rate(), 100.0);
+}
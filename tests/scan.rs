@@ -0,0 +1,186 @@
+mod common;
+
+use std::{collections::HashMap, time::{Duration, Instant}};
+
+use common::{dribbling_server, fixture, mock_server, status_packet};
+use futures::StreamExt;
+use quickie::{filter::Filter, perform_scan, scan_outcomes, scan_stream, ScanConfig, ScanError, Target};
+
+#[tokio::test]
+async fn scans_a_status_server() {
+    let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+
+    let result = perform_scan(&target, &ScanConfig { ping: true, ..Default::default() }).await.unwrap();
+
+    assert!(result.matched);
+    assert_eq!(result.motd.version.protocol, 763);
+    assert!(result.ping_ms.is_some());
+}
+
+#[tokio::test]
+async fn filtered_out_servers_are_not_read_any_further() {
+    let status = status_packet(fixture("status_1_20.json"));
+    let addr = mock_server(status.clone()).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+
+    let matching = ScanConfig { ping: true, ..Default::default() };
+    let filtered = ScanConfig { ping: true, filter: Filter { protocol: Some(47), ..Default::default() }, ..Default::default() };
+
+    let matched = perform_scan(&target, &matching).await.unwrap();
+    let skipped = perform_scan(&target, &filtered).await.unwrap();
+
+    assert!(!skipped.matched);
+    assert_eq!(skipped.ping_ms, None);
+
+    // the filtered scan stops right after the status packet, the other one also reads the pong
+    assert_eq!(skipped.bytes_read, status.len() as u64);
+    assert_eq!(matched.bytes_read, status.len() as u64 + 10);
+}
+
+#[tokio::test]
+async fn oversized_status_is_its_own_error() {
+    let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+
+    let err = perform_scan(&target, &ScanConfig { max_packet_size: 64, ..Default::default() }).await.err().unwrap();
+
+    assert!(matches!(err, ScanError::PacketTooLarge(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn handshake_and_request_arrive_together() {
+    use quickie::protocol::Packet;
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    // answers only if a single read holds both the handshake and the status request
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0u8; 1024];
+        let len = stream.read(&mut buf).await.unwrap();
+
+        let mut received = &buf[..len];
+        let handshake = Packet::decode(&mut received).unwrap();
+        let request = Packet::decode(&mut received).unwrap();
+        assert_eq!((handshake.id, request), (0x00, Packet::new(0x00, vec![])));
+
+        stream.write_all(&status_packet(fixture("status_1_20.json"))).await.unwrap();
+    });
+
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+    let result = perform_scan(&target, &ScanConfig::default()).await.unwrap();
+
+    assert_eq!(result.motd.version.protocol, 763);
+}
+
+#[tokio::test]
+async fn the_richest_protocol_candidate_wins() {
+    // old clients get a bare status without the player sample and favicon
+    let addr = common::mock_server_with(|protocol| match protocol {
+        763 => status_packet(fixture("status_1_20.json")),
+        _ => status_packet(r#"{"version":{"name":"Paper 1.20.1","protocol":763},"players":{"max":500,"online":1312},"description":"A Minecraft Server"}"#)
+    }).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+
+    let config = ScanConfig { protocol_candidates: vec![47, 763, 340], ..Default::default() };
+    let result = perform_scan(&target, &config).await.unwrap();
+
+    assert_eq!(result.candidate_protocol, Some(763));
+    assert!(result.motd.favicon.is_some());
+}
+
+#[tokio::test]
+async fn http_answers_are_not_minecraft() {
+    let http = b"HTTP/1.1 400 Bad Request\r\nContent-Type: text/html\r\nContent-Length: 11\r\nConnection: close\r\n\r\nBad Request".to_vec();
+    let addr = mock_server(http).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+
+    let err = perform_scan(&target, &ScanConfig { legacy_fallback: true, ..Default::default() }).await.err().unwrap();
+
+    assert!(matches!(err, ScanError::NotMinecraft));
+}
+
+#[tokio::test]
+async fn results_carry_the_scan_label() {
+    let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+
+    let result = perform_scan(&target, &ScanConfig { label: Some("spring-2024".into()), ..Default::default() }).await.unwrap();
+
+    assert_eq!(result.campaign.as_deref(), Some("spring-2024"));
+    assert_eq!(serde_json::to_value(&result).unwrap()["campaign"], "spring-2024");
+}
+
+#[tokio::test]
+async fn slow_servers_hit_the_scan_deadline() {
+    // every byte arrives well within the read timeout, the whole status never does
+    let read_timeout = Duration::from_millis(200);
+    let addr = dribbling_server(status_packet(fixture("status_1_20.json")), read_timeout / 2).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+
+    let config = ScanConfig { read_timeout, per_scan_deadline: Duration::from_secs(1), ..Default::default() };
+    let started = Instant::now();
+    let err = perform_scan(&target, &config).await.err().unwrap();
+    let elapsed = started.elapsed();
+
+    assert!(matches!(err, ScanError::ScanTimeout(deadline) if deadline == config.per_scan_deadline), "{:?}", err);
+    assert!(elapsed >= config.per_scan_deadline && elapsed < Duration::from_millis(1500), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn follow_up_probes_run_once_per_scan() {
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    use tokio::net::UdpSocket;
+
+    let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+    let target = Target { ip: addr.ip().to_string(), port: addr.port() };
+
+    // the query goes to the same port over UDP, a junk answer fails it right away
+    let queries = Arc::new(AtomicUsize::new(0));
+    let socket = UdpSocket::bind(addr).await.unwrap();
+    tokio::spawn({
+        let queries = queries.clone();
+        async move {
+            let mut buffer = [0u8; 64];
+            while let Ok((_, client)) = socket.recv_from(&mut buffer).await {
+                queries.fetch_add(1, Ordering::Relaxed);
+                socket.send_to(b"junk", client).await.ok();
+            }
+        }
+    });
+
+    // two candidates and a re-probe in the server's own protocol, three statuses in total
+    let config = ScanConfig { protocol_candidates: vec![47, 340], auto_version: true, query: true, ..Default::default() };
+    let result = perform_scan(&target, &config).await.unwrap();
+
+    assert_eq!(result.reprobed_protocol, Some(763));
+    assert_eq!(queries.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn every_streamed_target_comes_out() {
+    let mut targets = vec![];
+    for _ in 0..5 {
+        let addr = mock_server(status_packet(fixture("status_1_20.json"))).await;
+        targets.push(Target { ip: addr.ip().to_string(), port: addr.port() });
+    }
+    // a port nothing listens on anymore
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    targets.push(Target { ip: closed.ip().to_string(), port: closed.port() });
+
+    // fewer in flight than there are targets
+    let config = ScanConfig { concurrency: 2, ..Default::default() };
+
+    let outcomes: HashMap<Target, Result<_, _>> = scan_outcomes(targets.clone(), config.clone()).collect().await;
+    assert_eq!(outcomes.len(), targets.len());
+    for target in &targets[..5] {
+        assert_eq!(outcomes[target].as_ref().unwrap().port, target.port);
+    }
+    assert!(matches!(outcomes[&targets[5]], Err(ScanError::Connect(_))));
+
+    let results: Vec<_> = scan_stream(targets.clone(), config).collect().await;
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 5);
+    assert_eq!(results.iter().filter(|result| result.is_err()).count(), 1);
+}
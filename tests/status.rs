@@ -0,0 +1,52 @@
+mod common;
+
+use bytes::Bytes;
+use common::{fixture, status_packet};
+use quickie::status::parse_status_response;
+
+#[test]
+fn parses_standard_status_response() {
+    let motd = parse_status_response(status_packet(fixture("status_1_20.json")).into(), false).unwrap().motd;
+    assert_eq!(motd.version.protocol, 763);
+}
+
+#[test]
+fn falls_back_to_raw_json_without_string_length() {
+    let motd = parse_status_response(fixture("status_no_string_length.bin").into(), false).unwrap().motd;
+
+    assert_eq!(motd.version.protocol, 760);
+    assert_eq!(motd.description.plain_text(), "Broken but alive");
+}
+
+#[test]
+fn rejects_garbage() {
+    assert!(parse_status_response(Bytes::from_static(b"\x05\x00\x03abc"), true).is_err());
+}
+
+#[test]
+fn decodes_invalid_utf8_lossily_when_asked() {
+    let json = b"{\"version\":{\"name\":\"1.19.2\",\"protocol\":760},\"players\":{\"max\":20,\"online\":0},\"description\":{\"text\":\"caf\xe9\"}}";
+
+    assert!(parse_status_response(status_packet(json).into(), false).is_err());
+
+    let status = parse_status_response(status_packet(json).into(), true).unwrap();
+    assert!(status.lossy_utf8);
+    assert_eq!(status.motd.description.plain_text(), "caf\u{fffd}");
+}
+
+#[test]
+fn lossy_decoding_does_not_hide_broken_json() {
+    assert!(parse_status_response(status_packet(b"{\"version\": \xe9").into(), true).is_err());
+}
+
+#[test]
+fn other_packets_are_not_a_status() {
+    use quickie::{protocol::Packet, ScanError};
+
+    // a perfectly fine status body, just under the wrong id
+    let mut response = Bytes::from(status_packet(fixture("status_1_20.json")));
+    let data = Packet::decode(&mut response).unwrap().data;
+    let packet = Packet::new(0x1b, data).encode().freeze();
+
+    assert!(matches!(parse_status_response(packet, false), Err(ScanError::UnexpectedPacket(0x1b))));
+}
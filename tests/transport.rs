@@ -0,0 +1,78 @@
+mod common;
+
+use std::{io, pin::Pin, sync::Mutex, task::{Context, Poll}};
+
+use common::{fixture, serve, status_packet};
+use quickie::{perform_scan_with, transport::Transport, ScanConfig, ScanError, Target};
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+
+// ips of the targets whose connection was shut down (sent a FIN)
+static SHUT_DOWN: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// An in-memory connection, every connect gets a fresh mock server on the other end.
+struct Duplex(DuplexStream, String);
+
+impl Transport for Duplex {
+    async fn connect(target: &Target, _: &ScanConfig) -> Result<Self, ScanError> {
+        if target.port == 0 {
+            return Err(ScanError::Connect(io::ErrorKind::ConnectionRefused.into()));
+        }
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(serve(server, |_| status_packet(fixture("status_1_20.json"))));
+        Ok(Duplex(client, target.ip.clone()))
+    }
+}
+
+impl AsyncRead for Duplex {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Duplex {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        SHUT_DOWN.lock().unwrap().push(self.1.clone());
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+#[tokio::test]
+async fn scans_over_an_in_memory_transport() {
+    let target = Target { ip: "mock".into(), port: 25565 };
+
+    let result = perform_scan_with::<Duplex>(&target, &ScanConfig { ping: true, ..Default::default() }).await.unwrap();
+
+    assert_eq!(result.motd.version.protocol, 763);
+    assert!(result.ping_ms.is_some());
+}
+
+#[tokio::test]
+async fn transport_connect_errors_fail_the_scan() {
+    let target = Target { ip: "mock".into(), port: 0 };
+
+    let err = perform_scan_with::<Duplex>(&target, &ScanConfig::default()).await.err().unwrap();
+
+    assert!(matches!(err, ScanError::Connect(_)));
+}
+
+#[tokio::test]
+async fn reset_on_close_skips_the_fin() {
+    let config = ScanConfig { ping: true, ..Default::default() };
+    perform_scan_with::<Duplex>(&Target { ip: "fin".into(), port: 25565 }, &config).await.unwrap();
+
+    let config = ScanConfig { reset_on_close: true, ..config };
+    perform_scan_with::<Duplex>(&Target { ip: "reset".into(), port: 25565 }, &config).await.unwrap();
+
+    let shut_down = SHUT_DOWN.lock().unwrap();
+    assert!(shut_down.iter().any(|ip| ip == "fin"));
+    assert!(!shut_down.iter().any(|ip| ip == "reset"));
+}